@@ -0,0 +1,100 @@
+//! Per-bucket statistics over a numeric field of a container's readings,
+//! computed in Postgres so charts don't have to download raw rows.
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use sea_orm::{ConnectionTrait, DbErr, FromQueryResult, IdenStatic, Statement};
+use serde::{Deserialize, Serialize};
+
+use crate::{filter::parse_path, pagination::TimeField};
+
+/// `{time}` is replaced by the column the query ranges over. Readings whose
+/// field is missing or not a number are left out.
+const AGGREGATE_SQL: &str = r#"
+SELECT
+    date_trunc($2, time) AS bucket,
+    MIN(value) AS min,
+    MAX(value) AS max,
+    AVG(value) AS avg,
+    SUM(value) AS sum,
+    COUNT(*) AS count
+FROM (
+    SELECT {time} AS time, (data::jsonb #>> $3)::float8 AS value
+    FROM sensor_data
+    WHERE container_id = $1
+        AND jsonb_typeof(data::jsonb #> $3) = 'number'
+        AND ($4::timestamp IS NULL OR {time} >= $4)
+        AND ($5::timestamp IS NULL OR {time} < $5)
+) AS samples
+GROUP BY bucket
+ORDER BY bucket
+"#;
+
+#[derive(Clone, Copy, Deserialize)]
+pub enum Bucket {
+    #[serde(rename = "1m")]
+    Minute,
+    #[serde(rename = "1h")]
+    Hour,
+    #[serde(rename = "1d")]
+    Day,
+}
+
+impl Bucket {
+    /// Field name understood by Postgres' `date_trunc`.
+    fn unit(self) -> &'static str {
+        match self {
+            Bucket::Minute => "minute",
+            Bucket::Hour => "hour",
+            Bucket::Day => "day",
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct AggregateQuery {
+    /// Dotted path into `data`, as in subscription conditions.
+    pub field: String,
+    pub bucket: Bucket,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub time: TimeField,
+}
+
+#[derive(Serialize, FromQueryResult)]
+pub struct BucketStats {
+    pub bucket: NaiveDateTime,
+    pub min: f64,
+    pub max: f64,
+    pub avg: f64,
+    pub sum: f64,
+    pub count: i64,
+}
+
+impl AggregateQuery {
+    pub fn path(&self) -> Result<Vec<String>, String> {
+        parse_path(&self.field)
+    }
+
+    /// Buckets without samples are omitted.
+    pub async fn run<C: ConnectionTrait>(
+        &self,
+        db: &C,
+        container_id: &str,
+        path: Vec<String>,
+    ) -> Result<Vec<BucketStats>, DbErr> {
+        BucketStats::find_by_statement(Statement::from_sql_and_values(
+            db.get_database_backend(),
+            AGGREGATE_SQL.replace("{time}", self.time.column().as_str()),
+            [
+                container_id.into(),
+                self.bucket.unit().into(),
+                path.into(),
+                self.from.map(|from| from.naive_utc()).into(),
+                self.to.map(|to| to.naive_utc()).into(),
+            ],
+        ))
+        .all(db)
+        .await
+    }
+}
@@ -0,0 +1,396 @@
+//! Threshold alarms: rules on a container or on every container of a sensor,
+//! evaluated against each stored reading.
+//!
+//! A rule raises an alarm once `field <operator> threshold` has held for
+//! `duration_seconds` of consecutive readings, by when they were measured.
+//! The alarm stays open, raised or acknowledged, until a reading is past the
+//! threshold by `hysteresis` in the other direction, which clears it. Each
+//! transition is recorded as an `alarm.*` event for subscribers and live
+//! consumers.
+
+use std::collections::HashMap;
+
+use chrono::{NaiveDateTime, TimeDelta, Utc};
+use nanoid::nanoid;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, Condition as SqlCondition, ConnectionTrait, DbErr, EntityTrait,
+    QueryFilter, QueryOrder, QuerySelect, sea_query::OnConflict,
+};
+use serde_json::Value;
+
+use crate::{
+    entities::{
+        prelude::*,
+        sea_orm_active_enums::{AlarmOperator, AlarmState},
+        *,
+    },
+    events::record,
+    filter::{Condition, Operator, lookup, parse_path},
+    live::LiveEvent,
+    notification::{envelope::EventType, scope::Resource},
+};
+
+impl alarm_rule::Model {
+    /// When the rule is breached.
+    pub fn condition(&self) -> Condition {
+        self.compare(operator(self.operator), self.threshold)
+    }
+
+    /// When an open alarm of the rule clears: the inverse of
+    /// [`Self::condition`], moved away from the threshold by the hysteresis.
+    /// `eq` and `ne` rules ignore the hysteresis.
+    pub fn clear_condition(&self) -> Condition {
+        match self.operator {
+            AlarmOperator::Gt => self.compare(Operator::Lte, self.threshold - self.hysteresis),
+            AlarmOperator::Gte => self.compare(Operator::Lt, self.threshold - self.hysteresis),
+            AlarmOperator::Lt => self.compare(Operator::Gte, self.threshold + self.hysteresis),
+            AlarmOperator::Lte => self.compare(Operator::Gt, self.threshold + self.hysteresis),
+            AlarmOperator::Eq => self.compare(Operator::Ne, self.threshold),
+            AlarmOperator::Ne => self.compare(Operator::Eq, self.threshold),
+        }
+    }
+
+    fn compare(&self, operator: Operator, threshold: f64) -> Condition {
+        Condition {
+            path: parse_path(&self.field).unwrap_or_default(),
+            operator,
+            value: threshold.into(),
+        }
+    }
+}
+
+fn operator(operator: AlarmOperator) -> Operator {
+    match operator {
+        AlarmOperator::Eq => Operator::Eq,
+        AlarmOperator::Ne => Operator::Ne,
+        AlarmOperator::Gt => Operator::Gt,
+        AlarmOperator::Gte => Operator::Gte,
+        AlarmOperator::Lt => Operator::Lt,
+        AlarmOperator::Lte => Operator::Lte,
+    }
+}
+
+/// Checks a rule before it is stored.
+pub fn check_rule(
+    field: &str,
+    threshold: f64,
+    duration_seconds: i64,
+    hysteresis: f64,
+) -> Result<(), String> {
+    parse_path(field)?;
+    if !threshold.is_finite() {
+        return Err("Threshold must be a finite number".to_owned());
+    }
+    if duration_seconds < 0 {
+        return Err("Duration must not be negative".to_owned());
+    }
+    if !hysteresis.is_finite() || hysteresis < 0.0 {
+        return Err("Hysteresis must be a non-negative number".to_owned());
+    }
+    Ok(())
+}
+
+/// Evaluates the enabled rules of `container` and its sensor against a
+/// reading just stored on `db`, raising and clearing alarms. Run on the
+/// inserting transaction, after the insert.
+///
+/// Each rule keeps an `alarm_rule_state` row per container with when the
+/// current breach started, by `measured_at`; a reading measured before the
+/// last one evaluated arrived late and is ignored, as it cannot extend or
+/// break a streak already decided.
+///
+/// The rules are share-locked, so [`retire`] waits for evaluations in flight,
+/// and their state rows for the container are locked, so concurrent inserts
+/// into the container cannot raise the same alarm twice while inserts into
+/// the sensor's other containers go on. Both are locked in id order, before
+/// anything else an insert locks, so call this before recording the reading.
+pub async fn evaluate<C: ConnectionTrait>(
+    db: &C,
+    container: &data_container::Model,
+    entity: &sensor_data::Model,
+) -> Result<Vec<LiveEvent>, DbErr> {
+    let rules = AlarmRule::find()
+        .filter(alarm_rule::Column::Enabled.eq(true))
+        .filter(
+            SqlCondition::any()
+                .add(alarm_rule::Column::ContainerId.eq(&container.id))
+                .add(alarm_rule::Column::SensorId.eq(&container.sensor_id)),
+        )
+        .order_by_asc(alarm_rule::Column::Id)
+        .lock_shared()
+        .all(db)
+        .await?;
+    if rules.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    // A missing state row starts out as if this reading was the last one
+    // evaluated, outside any breach.
+    AlarmRuleState::insert_many(rules.iter().map(|rule| alarm_rule_state::ActiveModel {
+        rule_id: sea_orm::ActiveValue::Set(rule.id.to_owned()),
+        container_id: sea_orm::ActiveValue::Set(container.id.to_owned()),
+        breach_started_at: sea_orm::ActiveValue::Set(None),
+        last_measured_at: sea_orm::ActiveValue::Set(entity.measured_at),
+    }))
+    .on_conflict_do_nothing()
+    .exec(db)
+    .await?;
+    let mut states: HashMap<String, alarm_rule_state::Model> = AlarmRuleState::find()
+        .filter(alarm_rule_state::Column::RuleId.is_in(rules.iter().map(|rule| &rule.id)))
+        .filter(alarm_rule_state::Column::ContainerId.eq(&container.id))
+        .order_by_asc(alarm_rule_state::Column::RuleId)
+        .lock_exclusive()
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|state| (state.rule_id.to_owned(), state))
+        .collect();
+
+    let mut open: HashMap<String, alarm::Model> = Alarm::find()
+        .filter(alarm::Column::RuleId.is_in(rules.iter().map(|rule| &rule.id)))
+        .filter(alarm::Column::ContainerId.eq(&container.id))
+        .filter(alarm::Column::State.ne(AlarmState::Cleared))
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|alarm| (alarm.rule_id.to_owned(), alarm))
+        .collect();
+
+    let data = entity.data.as_ref().unwrap_or(&Value::Null);
+    let mut events = Vec::new();
+    for rule in rules {
+        let state = states.remove(&rule.id);
+        if state
+            .as_ref()
+            .is_some_and(|state| entity.measured_at < state.last_measured_at)
+        {
+            continue;
+        }
+        let mut breach_started_at = state.and_then(|state| state.breach_started_at);
+
+        match open.remove(&rule.id) {
+            Some(alarm) => {
+                breach_started_at = None;
+                if rule.clear_condition().matches(data) {
+                    let (_, event) = transition(db, alarm, AlarmState::Cleared).await?;
+                    events.extend(event);
+                }
+            }
+            None => {
+                let condition = rule.condition();
+                if condition.matches(data) {
+                    let started = *breach_started_at.get_or_insert(entity.measured_at);
+                    if entity.measured_at - started >= TimeDelta::seconds(rule.duration_seconds) {
+                        breach_started_at = None;
+                        events.extend(raise(db, &rule, &condition, entity).await?);
+                    }
+                } else {
+                    breach_started_at = None;
+                }
+            }
+        }
+
+        save_state(
+            db,
+            &rule.id,
+            &container.id,
+            breach_started_at,
+            entity.measured_at,
+        )
+        .await?;
+    }
+    Ok(events)
+}
+
+async fn save_state<C: ConnectionTrait>(
+    db: &C,
+    rule_id: &str,
+    container_id: &str,
+    breach_started_at: Option<NaiveDateTime>,
+    last_measured_at: NaiveDateTime,
+) -> Result<(), DbErr> {
+    let state = alarm_rule_state::ActiveModel {
+        rule_id: sea_orm::ActiveValue::Set(rule_id.to_owned()),
+        container_id: sea_orm::ActiveValue::Set(container_id.to_owned()),
+        breach_started_at: sea_orm::ActiveValue::Set(breach_started_at),
+        last_measured_at: sea_orm::ActiveValue::Set(last_measured_at),
+    };
+    AlarmRuleState::insert(state)
+        .on_conflict(
+            OnConflict::columns([
+                alarm_rule_state::Column::RuleId,
+                alarm_rule_state::Column::ContainerId,
+            ])
+            .update_columns([
+                alarm_rule_state::Column::BreachStartedAt,
+                alarm_rule_state::Column::LastMeasuredAt,
+            ])
+            .to_owned(),
+        )
+        .exec(db)
+        .await?;
+    Ok(())
+}
+
+async fn raise<C: ConnectionTrait>(
+    db: &C,
+    rule: &alarm_rule::Model,
+    condition: &Condition,
+    entity: &sensor_data::Model,
+) -> Result<Option<LiveEvent>, DbErr> {
+    let data = entity.data.as_ref().unwrap_or(&Value::Null);
+    let alarm = alarm::ActiveModel {
+        id: sea_orm::ActiveValue::Set(nanoid!(10)),
+        rule_id: sea_orm::ActiveValue::Set(rule.id.to_owned()),
+        container_id: sea_orm::ActiveValue::Set(entity.container_id.to_owned()),
+        sensor_data_id: sea_orm::ActiveValue::Set(Some(entity.id.to_owned())),
+        state: sea_orm::ActiveValue::Set(AlarmState::Raised),
+        severity: sea_orm::ActiveValue::Set(rule.severity),
+        value: sea_orm::ActiveValue::Set(lookup(data, &condition.path).cloned()),
+        raised_at: sea_orm::ActiveValue::Set(Utc::now().naive_utc()),
+        ..Default::default()
+    }
+    .insert(db)
+    .await?;
+    record(db, EventType::AlarmRaised, Resource::Alarm(&alarm)).await
+}
+
+/// Clears the open alarms of a rule being disabled or deleted, recording
+/// `alarm.cleared` for each, and forgets its breach streaks. Run on the
+/// transaction making the change, before a deletion.
+pub async fn retire<C: ConnectionTrait>(db: &C, rule_id: &str) -> Result<Vec<LiveEvent>, DbErr> {
+    // Waits for evaluations in flight so none raises an alarm behind us.
+    AlarmRule::find_by_id(rule_id)
+        .lock_exclusive()
+        .one(db)
+        .await?;
+
+    let open = Alarm::find()
+        .filter(alarm::Column::RuleId.eq(rule_id))
+        .filter(alarm::Column::State.ne(AlarmState::Cleared))
+        .all(db)
+        .await?;
+    let mut events = Vec::new();
+    for alarm in open {
+        let (_, event) = transition(db, alarm, AlarmState::Cleared).await?;
+        events.extend(event);
+    }
+    reset(db, rule_id).await?;
+    Ok(events)
+}
+
+/// Forgets the breach streaks of a rule, so the next readings evaluate it
+/// afresh. Run once the rule is locked, e.g. by the update changing it.
+pub async fn reset<C: ConnectionTrait>(db: &C, rule_id: &str) -> Result<(), DbErr> {
+    AlarmRuleState::delete_many()
+        .filter(alarm_rule_state::Column::RuleId.eq(rule_id))
+        .exec(db)
+        .await?;
+    Ok(())
+}
+
+/// Moves an open alarm to `Acknowledged` or `Cleared` and records the event.
+/// Callers check that the transition is allowed.
+pub async fn transition<C: ConnectionTrait>(
+    db: &C,
+    alarm: alarm::Model,
+    state: AlarmState,
+) -> Result<(alarm::Model, Option<LiveEvent>), DbErr> {
+    let now = Utc::now().naive_utc();
+    let mut alarm: alarm::ActiveModel = alarm.into();
+    alarm.state = sea_orm::ActiveValue::Set(state);
+    let event_type = match state {
+        AlarmState::Acknowledged => {
+            alarm.acknowledged_at = sea_orm::ActiveValue::Set(Some(now));
+            EventType::AlarmAcknowledged
+        }
+        AlarmState::Cleared => {
+            alarm.cleared_at = sea_orm::ActiveValue::Set(Some(now));
+            EventType::AlarmCleared
+        }
+        AlarmState::Raised => EventType::AlarmRaised,
+    };
+    let alarm = alarm.update(db).await?;
+    let event = record(db, event_type, Resource::Alarm(&alarm)).await?;
+    Ok((alarm, event))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+    use serde_json::json;
+
+    use super::*;
+    use crate::entities::sea_orm_active_enums::AlarmSeverity;
+
+    fn rule(operator: AlarmOperator, threshold: f64, hysteresis: f64) -> alarm_rule::Model {
+        alarm_rule::Model {
+            id: "rule".to_owned(),
+            container_id: Some("container".to_owned()),
+            sensor_id: None,
+            name: "Too hot".to_owned(),
+            field: "climate.temperature".to_owned(),
+            operator,
+            threshold,
+            duration_seconds: 0,
+            hysteresis,
+            severity: AlarmSeverity::Warning,
+            enabled: true,
+            create_at: DateTime::UNIX_EPOCH.naive_utc(),
+        }
+    }
+
+    fn reading(temperature: f64) -> Value {
+        json!({ "climate": { "temperature": temperature } })
+    }
+
+    #[test]
+    fn clears_past_the_hysteresis() {
+        let above = rule(AlarmOperator::Gt, 30.0, 2.0);
+        assert!(above.condition().matches(&reading(30.5)));
+        assert!(!above.condition().matches(&reading(30.0)));
+        // Between the threshold and the hysteresis the alarm stays open.
+        assert!(!above.clear_condition().matches(&reading(29.0)));
+        assert!(above.clear_condition().matches(&reading(28.0)));
+
+        let below = rule(AlarmOperator::Lte, 10.0, 1.5);
+        assert!(below.condition().matches(&reading(10.0)));
+        assert!(!below.clear_condition().matches(&reading(11.5)));
+        assert!(below.clear_condition().matches(&reading(11.6)));
+    }
+
+    #[test]
+    fn inverts_every_operator() {
+        let cases = [
+            (AlarmOperator::Gt, Operator::Lte, 28.0),
+            (AlarmOperator::Gte, Operator::Lt, 28.0),
+            (AlarmOperator::Lt, Operator::Gte, 32.0),
+            (AlarmOperator::Lte, Operator::Gt, 32.0),
+            (AlarmOperator::Eq, Operator::Ne, 30.0),
+            (AlarmOperator::Ne, Operator::Eq, 30.0),
+        ];
+        for (operator, inverse, threshold) in cases {
+            let clear = rule(operator, 30.0, 2.0).clear_condition();
+            assert_eq!(clear.path, ["climate", "temperature"]);
+            assert_eq!(clear.operator, inverse);
+            assert_eq!(clear.value, json!(threshold));
+        }
+    }
+
+    #[test]
+    fn missing_fields_neither_breach_nor_clear() {
+        let breach = rule(AlarmOperator::Gt, 30.0, 0.0);
+        let data = json!({ "humidity": 40 });
+        assert!(!breach.condition().matches(&data));
+        assert!(!breach.clear_condition().matches(&data));
+    }
+
+    #[test]
+    fn checks_rules() {
+        assert!(check_rule("climate.temperature", 30.0, 60, 0.5).is_ok());
+        assert!(check_rule("climate..temperature", 30.0, 60, 0.5).is_err());
+        assert!(check_rule("temperature", f64::NAN, 60, 0.5).is_err());
+        assert!(check_rule("temperature", 30.0, -1, 0.5).is_err());
+        assert!(check_rule("temperature", 30.0, 60, -0.5).is_err());
+    }
+}
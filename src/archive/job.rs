@@ -0,0 +1,136 @@
+use std::collections::BTreeMap;
+
+use actix_web::rt;
+use chrono::{NaiveDate, NaiveDateTime, TimeDelta, Utc};
+use redis::{AsyncCommands, Client, RedisResult};
+use sea_orm::{
+    ColumnTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter, QueryOrder, QuerySelect,
+    TransactionTrait,
+};
+
+use super::{ArchiveConfig, write_part};
+use crate::{
+    entities::{prelude::*, *},
+    routes::{DataContainer::LATEST_PREFIX, SensorData::uncache},
+    utils::get_redis_id,
+};
+
+/// Readings deleted per statement, well under Postgres' bind parameter limit.
+const DELETE_CHUNK_SIZE: usize = 1000;
+
+/// Starts the background task that moves readings past their container's
+/// `retention_period` into Parquet files. Each run moves batches of
+/// `batch_size` readings, each deleted in its own transaction, until nothing
+/// is due.
+///
+/// Parts are written before the rows are deleted, so a crash in between only
+/// leaves duplicates behind, which `read_range` drops. Archiving is not an
+/// eviction: no notifications or live events are sent, but the archived rows
+/// are dropped from the caches.
+pub fn spawn(db: DatabaseConnection, redis: Client, config: ArchiveConfig) {
+    rt::spawn(async move {
+        let mut interval = rt::time::interval(config.interval);
+
+        loop {
+            interval.tick().await;
+            if let Err(e) = archive_due(&db, &redis, &config).await {
+                eprintln!("Error archiving sensor data: {:?}", e);
+            }
+        }
+    });
+}
+
+async fn archive_due(
+    db: &DatabaseConnection,
+    redis: &Client,
+    config: &ArchiveConfig,
+) -> Result<(), DbErr> {
+    let containers = DataContainer::find()
+        .filter(data_container::Column::RetentionPeriod.is_not_null())
+        .all(db)
+        .await?;
+
+    for container in containers {
+        let Some(retention_period) = container.retention_period else {
+            continue;
+        };
+        let cutoff = Utc::now().naive_utc() - TimeDelta::seconds(retention_period);
+        while archive_batch(db, redis, config, &container.id, cutoff).await? {}
+    }
+    Ok(())
+}
+
+/// Moves the oldest `batch_size` readings of a container created before
+/// `cutoff` into the archive. Returns whether a full batch was moved, i.e.
+/// whether more may be due.
+async fn archive_batch(
+    db: &DatabaseConnection,
+    redis: &Client,
+    config: &ArchiveConfig,
+    container_id: &String,
+    cutoff: NaiveDateTime,
+) -> Result<bool, DbErr> {
+    let entities = SensorData::find()
+        .filter(sensor_data::Column::ContainerId.eq(container_id))
+        .filter(sensor_data::Column::CreatedAt.lt(cutoff))
+        .order_by_asc(sensor_data::Column::CreatedAt)
+        .order_by_asc(sensor_data::Column::Id)
+        .limit(config.batch_size)
+        .all(db)
+        .await?;
+    let full = !entities.is_empty() && entities.len() as u64 == config.batch_size;
+
+    let mut days: BTreeMap<NaiveDate, Vec<sensor_data::Model>> = BTreeMap::new();
+    for entity in entities {
+        days.entry(entity.created_at.date())
+            .or_default()
+            .push(entity);
+    }
+
+    for (day, entities) in days {
+        let job_config = config.clone();
+        let part_container_id = container_id.to_owned();
+        let written = rt::task::spawn_blocking(move || {
+            write_part(&job_config, &part_container_id, day, &entities).map(|_| entities)
+        })
+        .await;
+        let entities = match written {
+            Ok(Ok(entities)) => entities,
+            Ok(Err(e)) => {
+                eprintln!("Error writing archive for {}: {:?}", container_id, e);
+                return Ok(false);
+            }
+            Err(e) => {
+                eprintln!("Error writing archive for {}: {:?}", container_id, e);
+                return Ok(false);
+            }
+        };
+
+        let ids: Vec<String> = entities.into_iter().map(|entity| entity.id).collect();
+        let txn = db.begin().await?;
+        for chunk in ids.chunks(DELETE_CHUNK_SIZE) {
+            SensorData::delete_many()
+                .filter(sensor_data::Column::Id.is_in(chunk))
+                .exec(&txn)
+                .await?;
+        }
+        txn.commit().await?;
+
+        if let Err(e) = uncache_archived(redis, container_id, &ids).await {
+            eprintln!("Error uncaching archived sensor data: {:?}", e);
+        }
+    }
+    Ok(full)
+}
+
+async fn uncache_archived(
+    redis: &Client,
+    container_id: &String,
+    ids: &[String],
+) -> RedisResult<()> {
+    let mut redis_conn = redis.get_multiplexed_tokio_connection().await?;
+    uncache(&mut redis_conn, ids).await?;
+    redis_conn
+        .del(get_redis_id(LATEST_PREFIX, container_id))
+        .await
+}
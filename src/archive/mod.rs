@@ -0,0 +1,453 @@
+//! Archival of readings past their container's `retention_period` to Parquet
+//! files laid out as `<ARCHIVE_DIR>/<container id>/<YYYY-MM-DD>/<part>.parquet`,
+//! partitioned by `created_at`.
+
+use std::{
+    collections::HashSet,
+    fs::{self, File},
+    io::ErrorKind,
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use arrow_array::{Array, RecordBatch, StringArray, TimestampMicrosecondArray};
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use nanoid::nanoid;
+use parquet::{
+    arrow::{ArrowWriter, ProjectionMask, arrow_reader::ParquetRecordBatchReaderBuilder},
+    basic::Compression,
+    errors::ParquetError,
+    file::{properties::WriterProperties, statistics::Statistics},
+};
+use serde::Deserialize;
+
+use crate::{entities::*, utils::env_or};
+
+pub mod job;
+
+const DAY_FORMAT: &str = "%Y-%m-%d";
+/// Rows per row group. Parts are written oldest first, so the `created_at`
+/// statistics of small row groups narrow a range down to few rows.
+const ROW_GROUP_SIZE: usize = 8192;
+pub const CONTENT_TYPE: &str = "application/vnd.apache.parquet";
+
+#[derive(Clone)]
+pub struct ArchiveConfig {
+    pub dir: PathBuf,
+    pub interval: Duration,
+    /// Readings moved per batch; a run moves batches until nothing is due.
+    pub batch_size: u64,
+    /// Archived readings a download may read at most.
+    pub max_rows: i64,
+}
+
+impl ArchiveConfig {
+    pub fn from_env() -> Self {
+        Self {
+            dir: PathBuf::from(env_or("ARCHIVE_DIR", "archive".to_owned())),
+            interval: Duration::from_secs(env_or("ARCHIVE_INTERVAL_SECONDS", 3600)),
+            batch_size: env_or("ARCHIVE_BATCH_SIZE", 10000),
+            max_rows: env_or("ARCHIVE_MAX_ROWS", 100000),
+        }
+    }
+
+    fn partition(&self, container_id: &str, day: NaiveDate) -> PathBuf {
+        self.dir
+            .join(container_id)
+            .join(day.format(DAY_FORMAT).to_string())
+    }
+}
+
+#[derive(Debug)]
+pub enum ReadError {
+    /// The range holds more than `max_rows` readings.
+    TooLarge,
+    Parquet(ParquetError),
+}
+
+impl From<ParquetError> for ReadError {
+    fn from(e: ParquetError) -> Self {
+        ReadError::Parquet(e)
+    }
+}
+
+impl From<std::io::Error> for ReadError {
+    fn from(e: std::io::Error) -> Self {
+        ReadError::Parquet(e.into())
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ArchiveQuery {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+}
+
+fn schema() -> SchemaRef {
+    let timestamp = DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into()));
+    Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Utf8, false),
+        Field::new("container_id", DataType::Utf8, false),
+        Field::new("created_at", timestamp.clone(), false),
+        Field::new("measured_at", timestamp, false),
+        // The reading's `data` as JSON text.
+        Field::new("data", DataType::Utf8, true),
+    ]))
+}
+
+/// Encodes readings as a Parquet file.
+pub fn encode(entities: &[sensor_data::Model]) -> Result<Vec<u8>, ParquetError> {
+    let mut buffer = Vec::new();
+    write(&mut buffer, entities)?;
+    Ok(buffer)
+}
+
+fn write(
+    writer: impl std::io::Write + Send,
+    entities: &[sensor_data::Model],
+) -> Result<(), ParquetError> {
+    let timestamps = |field: fn(&sensor_data::Model) -> NaiveDateTime| {
+        TimestampMicrosecondArray::from_iter_values(
+            entities
+                .iter()
+                .map(|entity| field(entity).and_utc().timestamp_micros()),
+        )
+        .with_timezone("UTC")
+    };
+    let batch = RecordBatch::try_new(
+        schema(),
+        vec![
+            Arc::new(StringArray::from_iter_values(
+                entities.iter().map(|entity| &entity.id),
+            )),
+            Arc::new(StringArray::from_iter_values(
+                entities.iter().map(|entity| &entity.container_id),
+            )),
+            Arc::new(timestamps(|entity| entity.created_at)),
+            Arc::new(timestamps(|entity| entity.measured_at)),
+            Arc::new(StringArray::from_iter(
+                entities
+                    .iter()
+                    .map(|entity| entity.data.as_ref().map(|data| data.to_string())),
+            )),
+        ],
+    )?;
+
+    let properties = WriterProperties::builder()
+        .set_compression(Compression::SNAPPY)
+        .set_max_row_group_size(ROW_GROUP_SIZE)
+        .build();
+    let mut writer = ArrowWriter::try_new(writer, schema(), Some(properties))?;
+    writer.write(&batch)?;
+    writer.close()?;
+    Ok(())
+}
+
+/// Writes readings of one container and day to a new part file. The file is
+/// only renamed into place once complete, so readers never see half of it.
+pub fn write_part(
+    config: &ArchiveConfig,
+    container_id: &str,
+    day: NaiveDate,
+    entities: &[sensor_data::Model],
+) -> Result<PathBuf, ParquetError> {
+    let partition = config.partition(container_id, day);
+    fs::create_dir_all(&partition)?;
+    let part = partition.join(format!("{}.parquet", nanoid!(16)));
+    let staging = part.with_extension("parquet.tmp");
+
+    let file = File::create(&staging)?;
+    write(&file, entities)?;
+    file.sync_all()?;
+    fs::rename(&staging, &part)?;
+    Ok(part)
+}
+
+/// Archived readings of a container created in `[from, to)`, oldest first.
+/// A part written again after a crash holds the same rows, so ids are
+/// deduplicated.
+///
+/// Only the `created_at` column of the row groups overlapping the range is
+/// read to count the readings in it, so a range over `max_rows` is refused
+/// without loading it.
+pub fn read_range(
+    config: &ArchiveConfig,
+    container_id: &str,
+    from: NaiveDateTime,
+    to: NaiveDateTime,
+) -> Result<Vec<sensor_data::Model>, ReadError> {
+    let parts = parts_in_range(config, container_id, from, to)?;
+    let range = from.and_utc().timestamp_micros()..to.and_utc().timestamp_micros();
+    let mut rows = 0;
+    for part in &parts {
+        rows += count_in_range(part, &range)?;
+        if rows > config.max_rows {
+            return Err(ReadError::TooLarge);
+        }
+    }
+
+    let mut entities = Vec::new();
+    let mut seen = HashSet::new();
+    for part in &parts {
+        for entity in read_part(open_in_range(part, &range)?)? {
+            if (from..to).contains(&entity.created_at) && seen.insert(entity.id.clone()) {
+                entities.push(entity);
+            }
+        }
+    }
+
+    entities.sort_by(|a, b| (a.created_at, &a.id).cmp(&(b.created_at, &b.id)));
+    Ok(entities)
+}
+
+/// Opens a part to read only the row groups whose `created_at` statistics
+/// overlap `range`, in microseconds.
+fn open_in_range(
+    path: &Path,
+    range: &Range<i64>,
+) -> Result<ParquetRecordBatchReaderBuilder<File>, ParquetError> {
+    let builder = ParquetRecordBatchReaderBuilder::try_new(File::open(path)?)?;
+    let column = created_at_column(&builder)?;
+    let row_groups = builder
+        .metadata()
+        .row_groups()
+        .iter()
+        .enumerate()
+        .filter(
+            |(_, row_group)| match row_group.column(column).statistics() {
+                Some(Statistics::Int64(statistics)) => {
+                    statistics.min_opt().is_none_or(|min| *min < range.end)
+                        && statistics.max_opt().is_none_or(|max| *max >= range.start)
+                }
+                _ => true,
+            },
+        )
+        .map(|(index, _)| index)
+        .collect();
+    Ok(builder.with_row_groups(row_groups))
+}
+
+/// Readings of a part created in `range`, in microseconds.
+fn count_in_range(path: &Path, range: &Range<i64>) -> Result<i64, ParquetError> {
+    let builder = open_in_range(path, range)?;
+    let projection =
+        ProjectionMask::leaves(builder.parquet_schema(), [created_at_column(&builder)?]);
+    let mut rows = 0;
+    for batch in builder.with_projection(projection).build()? {
+        let batch = batch?;
+        let created_at = column::<TimestampMicrosecondArray>(&batch, "created_at")?;
+        rows += created_at
+            .values()
+            .iter()
+            .filter(|micros| range.contains(micros))
+            .count() as i64;
+    }
+    Ok(rows)
+}
+
+fn created_at_column(
+    builder: &ParquetRecordBatchReaderBuilder<File>,
+) -> Result<usize, ParquetError> {
+    builder
+        .parquet_schema()
+        .columns()
+        .iter()
+        .position(|column| column.name() == "created_at")
+        .ok_or_else(|| ParquetError::General("Archive is missing column `created_at`".to_owned()))
+}
+
+/// The part files of the day partitions overlapping `[from, to)`.
+fn parts_in_range(
+    config: &ArchiveConfig,
+    container_id: &str,
+    from: NaiveDateTime,
+    to: NaiveDateTime,
+) -> Result<Vec<PathBuf>, std::io::Error> {
+    let mut parts = Vec::new();
+    let partitions = match fs::read_dir(config.dir.join(container_id)) {
+        Ok(partitions) => partitions,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(parts),
+        Err(e) => return Err(e),
+    };
+    for partition in partitions {
+        let partition = partition?.path();
+        let in_range = partition
+            .file_name()
+            .and_then(|name| NaiveDate::parse_from_str(name.to_str()?, DAY_FORMAT).ok())
+            .is_some_and(|day| (from.date()..=to.date()).contains(&day));
+        if !in_range {
+            continue;
+        }
+        for part in fs::read_dir(&partition)? {
+            let path = part?.path();
+            if path
+                .extension()
+                .is_some_and(|extension| extension == "parquet")
+            {
+                parts.push(path);
+            }
+        }
+    }
+    Ok(parts)
+}
+
+fn read_part(
+    builder: ParquetRecordBatchReaderBuilder<File>,
+) -> Result<Vec<sensor_data::Model>, ParquetError> {
+    let reader = builder.build()?;
+    let mut entities = Vec::new();
+    for batch in reader {
+        let batch = batch?;
+        let strings = |name: &str| column::<StringArray>(&batch, name);
+        let timestamps = |name: &str| column::<TimestampMicrosecondArray>(&batch, name);
+        let (ids, container_ids, data) =
+            (strings("id")?, strings("container_id")?, strings("data")?);
+        let (created_at, measured_at) = (timestamps("created_at")?, timestamps("measured_at")?);
+
+        for row in 0..batch.num_rows() {
+            entities.push(sensor_data::Model {
+                id: ids.value(row).to_owned(),
+                container_id: container_ids.value(row).to_owned(),
+                created_at: naive(created_at.value(row))?,
+                measured_at: naive(measured_at.value(row))?,
+                data: if data.is_null(row) {
+                    None
+                } else {
+                    Some(serde_json::from_str(data.value(row)).map_err(|e| {
+                        ParquetError::General(format!("Invalid archived data: {}", e))
+                    })?)
+                },
+            });
+        }
+    }
+    Ok(entities)
+}
+
+fn column<'a, T: 'static>(batch: &'a RecordBatch, name: &str) -> Result<&'a T, ParquetError> {
+    batch
+        .column_by_name(name)
+        .and_then(|column| column.as_any().downcast_ref::<T>())
+        .ok_or_else(|| ParquetError::General(format!("Archive is missing column `{}`", name)))
+}
+
+fn naive(micros: i64) -> Result<NaiveDateTime, ParquetError> {
+    DateTime::from_timestamp_micros(micros)
+        .map(|timestamp| timestamp.naive_utc())
+        .ok_or_else(|| ParquetError::General(format!("Invalid archived timestamp {}", micros)))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeDelta;
+    use serde_json::{Value, json};
+
+    use super::*;
+
+    fn config(max_rows: i64) -> ArchiveConfig {
+        ArchiveConfig {
+            dir: std::env::temp_dir().join(format!("archive-test-{}", nanoid!(8))),
+            interval: Duration::from_secs(3600),
+            batch_size: 100,
+            max_rows,
+        }
+    }
+
+    fn reading(id: &str, created_at: NaiveDateTime, data: Option<Value>) -> sensor_data::Model {
+        sensor_data::Model {
+            id: id.to_owned(),
+            container_id: "c1".to_owned(),
+            created_at,
+            measured_at: created_at - TimeDelta::seconds(5),
+            data,
+        }
+    }
+
+    fn at(day: u32, hour: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2025, 1, day)
+            .unwrap()
+            .and_hms_micro_opt(hour, 0, 0, 123456)
+            .unwrap()
+    }
+
+    #[test]
+    fn round_trips_readings() {
+        let config = config(100);
+        let first = vec![
+            reading("b", at(1, 12), Some(json!({ "temperature": 21.5 }))),
+            reading("a", at(1, 10), None),
+        ];
+        let second = vec![reading("c", at(2, 8), Some(json!([1, "two"])))];
+        write_part(&config, "c1", at(1, 0).date(), &first).unwrap();
+        write_part(&config, "c1", at(2, 0).date(), &second).unwrap();
+        // Written again as after a crash before the rows were deleted.
+        write_part(&config, "c1", at(2, 0).date(), &second).unwrap();
+
+        let read = read_range(&config, "c1", at(1, 0), at(3, 0)).unwrap();
+        assert_eq!(
+            read,
+            [first[1].clone(), first[0].clone(), second[0].clone()]
+        );
+
+        let read = read_range(&config, "c1", at(1, 11), at(2, 8)).unwrap();
+        assert_eq!(read, [first[0].clone()]);
+        assert!(
+            read_range(&config, "other", at(1, 0), at(3, 0))
+                .ok()
+                .unwrap()
+                .is_empty()
+        );
+        fs::remove_dir_all(&config.dir).unwrap();
+    }
+
+    #[test]
+    fn refuses_ranges_over_max_rows() {
+        let config = config(1);
+        let entities = vec![reading("a", at(1, 10), None), reading("b", at(1, 11), None)];
+        write_part(&config, "c1", at(1, 0).date(), &entities).unwrap();
+
+        assert!(matches!(
+            read_range(&config, "c1", at(1, 0), at(2, 0)),
+            Err(ReadError::TooLarge)
+        ));
+        fs::remove_dir_all(&config.dir).unwrap();
+    }
+
+    #[test]
+    fn counts_only_readings_in_range() {
+        let config = config(10);
+        let start = at(1, 0);
+        let entities: Vec<_> = (0..3 * ROW_GROUP_SIZE as i64)
+            .map(|second| {
+                reading(
+                    &second.to_string(),
+                    start + TimeDelta::seconds(second),
+                    None,
+                )
+            })
+            .collect();
+        write_part(&config, "c1", start.date(), &entities).unwrap();
+
+        let from = start + TimeDelta::seconds(ROW_GROUP_SIZE as i64 + 100);
+        let read = read_range(&config, "c1", from, from + TimeDelta::seconds(5)).unwrap();
+        assert_eq!(read, entities[ROW_GROUP_SIZE + 100..ROW_GROUP_SIZE + 105]);
+        assert!(matches!(
+            read_range(&config, "c1", start, from),
+            Err(ReadError::TooLarge)
+        ));
+        fs::remove_dir_all(&config.dir).unwrap();
+    }
+
+    #[test]
+    fn encodes_a_readable_file() {
+        let entities = vec![reading("a", at(1, 10), Some(json!({ "on": true })))];
+        let encoded = encode(&entities).unwrap();
+        let path = std::env::temp_dir().join(format!("archive-test-{}.parquet", nanoid!(8)));
+        fs::write(&path, encoded).unwrap();
+        let builder = ParquetRecordBatchReaderBuilder::try_new(File::open(&path).unwrap()).unwrap();
+        assert_eq!(read_part(builder).unwrap(), entities);
+        fs::remove_file(&path).unwrap();
+    }
+}
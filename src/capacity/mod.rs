@@ -0,0 +1,211 @@
+//! oneM2M-style container limits: `max_nr_of_instances`, `max_byte_size`
+//! (summed over the readings' `data` as JSON text) and `max_instance_age` in
+//! seconds. Readings over a limit are evicted oldest first.
+
+use chrono::{NaiveDateTime, TimeDelta, Utc};
+use sea_orm::{
+    ColumnTrait, ConnectionTrait, DbErr, EntityTrait, QueryFilter, QueryOrder, QuerySelect,
+    QueryTrait,
+    sea_query::{Expr, SelectStatement},
+};
+
+use crate::{
+    entities::{prelude::*, *},
+    events::record,
+    live::LiveEvent,
+    notification::{envelope::EventType, scope::Resource},
+};
+
+pub mod sweeper;
+
+const BYTES_SQL: &str = "octet_length(COALESCE(data::text, ''))::bigint";
+const TOTAL_BYTES_SQL: &str = "SUM(octet_length(COALESCE(data::text, '')))::bigint";
+
+/// Readings fetched per round while looking for enough bytes to free.
+const PAGE_SIZE: u64 = 100;
+/// Readings deleted per statement, which keeps eviction notifications and
+/// bind parameters bounded however far a container is over its limits.
+const CHUNK_SIZE: u64 = 1000;
+
+/// Evicts every reading of `container` over one of its limits. Run on the
+/// inserting transaction, after the insert. Returns the evicted ids, to drop
+/// from the caches, with the live events.
+pub async fn evict<C: ConnectionTrait>(
+    db: &C,
+    container: &data_container::Model,
+) -> Result<(Vec<String>, Vec<LiveEvent>), DbErr> {
+    let (mut ids, mut events) = expire(db, container).await?;
+    if container.max_nr_of_instances.is_some() || container.max_byte_size.is_some() {
+        let over = over_capacity(db, container).await?;
+        let (over, over_events) = remove_oldest(db, container, None, Some(over)).await?;
+        ids.extend(over);
+        events.extend(over_events);
+    }
+    Ok((ids, events))
+}
+
+/// How many of the oldest readings are past the count and size limits. Only
+/// the sizes of readings that have to go are read, and the newest one is
+/// never counted so an insert always keeps the reading it just stored.
+async fn over_capacity<C: ConnectionTrait>(
+    db: &C,
+    container: &data_container::Model,
+) -> Result<u64, DbErr> {
+    // Summing the sizes reads every row's data, so only do it when needed.
+    let total_bytes = match container.max_byte_size {
+        Some(_) => TOTAL_BYTES_SQL,
+        None => "0::bigint",
+    };
+    let (count, bytes): (i64, Option<i64>) = SensorData::find()
+        .select_only()
+        .column_as(sensor_data::Column::Id.count(), "count")
+        .column_as(Expr::cust(total_bytes), "bytes")
+        .filter(sensor_data::Column::ContainerId.eq(&container.id))
+        .into_tuple()
+        .one(db)
+        .await?
+        .unwrap_or_default();
+
+    let evictable = (count - 1).max(0) as u64;
+    let excess_count = container
+        .max_nr_of_instances
+        .map_or(0, |max| (count - max).max(0) as u64)
+        .min(evictable);
+    let excess_bytes = container
+        .max_byte_size
+        .map_or(0, |max| (bytes.unwrap_or(0) - max).max(0));
+
+    // Past the count excess, page through the sizes of the next oldest
+    // readings until enough bytes are freed.
+    let mut over = excess_count;
+    let mut freed: i64 = 0;
+    if excess_bytes > 0 && excess_count > 0 {
+        let (sized,): (Option<i64>,) = SensorData::find()
+            .select_only()
+            .column_as(Expr::cust(TOTAL_BYTES_SQL), "bytes")
+            .filter(sensor_data::Column::Id.in_subquery(oldest(container, None, 0, excess_count)))
+            .into_tuple()
+            .one(db)
+            .await?
+            .unwrap_or_default();
+        freed = sized.unwrap_or(0);
+    }
+    while freed < excess_bytes && over < evictable {
+        let sizes: Vec<i64> = SensorData::find()
+            .select_only()
+            .column_as(Expr::cust(BYTES_SQL), "bytes")
+            .filter(sensor_data::Column::ContainerId.eq(&container.id))
+            .order_by_asc(sensor_data::Column::CreatedAt)
+            .order_by_asc(sensor_data::Column::Id)
+            .offset(over)
+            .limit(PAGE_SIZE.min(evictable - over))
+            .into_tuple()
+            .all(db)
+            .await?;
+        if sizes.is_empty() {
+            break;
+        }
+        for size in sizes {
+            if freed >= excess_bytes {
+                break;
+            }
+            over += 1;
+            freed += size;
+        }
+    }
+    Ok(over)
+}
+
+/// Evicts the readings of `container` older than its `max_instance_age`.
+pub async fn expire<C: ConnectionTrait>(
+    db: &C,
+    container: &data_container::Model,
+) -> Result<(Vec<String>, Vec<LiveEvent>), DbErr> {
+    match expiry_cutoff(container) {
+        Some(cutoff) => remove_oldest(db, container, Some(cutoff), None).await,
+        None => Ok((Vec::new(), Vec::new())),
+    }
+}
+
+fn expiry_cutoff(container: &data_container::Model) -> Option<NaiveDateTime> {
+    container
+        .max_instance_age
+        .map(|age| Utc::now().naive_utc() - TimeDelta::seconds(age))
+}
+
+/// Ids of the `limit` oldest readings of `container` created before `cutoff`,
+/// skipping the first `offset`.
+fn oldest(
+    container: &data_container::Model,
+    cutoff: Option<NaiveDateTime>,
+    offset: u64,
+    limit: u64,
+) -> SelectStatement {
+    SensorData::find()
+        .select_only()
+        .column(sensor_data::Column::Id)
+        .filter(sensor_data::Column::ContainerId.eq(&container.id))
+        .apply_if(cutoff, |select, cutoff| {
+            select.filter(sensor_data::Column::CreatedAt.lt(cutoff))
+        })
+        .order_by_asc(sensor_data::Column::CreatedAt)
+        .order_by_asc(sensor_data::Column::Id)
+        .offset(offset)
+        .limit(limit)
+        .into_query()
+}
+
+/// Deletes the oldest readings of `container` created before `cutoff`, at
+/// most `limit` of them, `CHUNK_SIZE` at a time. Queues `sensor_data.deleted`
+/// for each when the container asks for eviction notifications.
+async fn remove_oldest<C: ConnectionTrait>(
+    db: &C,
+    container: &data_container::Model,
+    cutoff: Option<NaiveDateTime>,
+    limit: Option<u64>,
+) -> Result<(Vec<String>, Vec<LiveEvent>), DbErr> {
+    let mut ids = Vec::new();
+    let mut events = Vec::new();
+    loop {
+        let remaining = limit.map_or(CHUNK_SIZE, |limit| limit - ids.len() as u64);
+        let chunk = remaining.min(CHUNK_SIZE);
+        if chunk == 0 {
+            break;
+        }
+
+        let deleted: Vec<String> = if container.notify_on_eviction {
+            // The events reference the readings, so record them first.
+            let evicted = SensorData::find()
+                .filter(sensor_data::Column::Id.in_subquery(oldest(container, cutoff, 0, chunk)))
+                .order_by_asc(sensor_data::Column::CreatedAt)
+                .order_by_asc(sensor_data::Column::Id)
+                .all(db)
+                .await?;
+            for entity in &evicted {
+                let resource = Resource::SensorData(entity);
+                events.extend(record(db, EventType::SensorDataDeleted, resource).await?);
+            }
+            let deleted: Vec<String> = evicted.into_iter().map(|entity| entity.id).collect();
+            SensorData::delete_many()
+                .filter(sensor_data::Column::Id.is_in(&deleted))
+                .exec(db)
+                .await?;
+            deleted
+        } else {
+            SensorData::delete_many()
+                .filter(sensor_data::Column::Id.in_subquery(oldest(container, cutoff, 0, chunk)))
+                .exec_with_returning(db)
+                .await?
+                .into_iter()
+                .map(|entity| entity.id)
+                .collect()
+        };
+
+        let done = (deleted.len() as u64) < chunk;
+        ids.extend(deleted);
+        if done {
+            break;
+        }
+    }
+    Ok((ids, events))
+}
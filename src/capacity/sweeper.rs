@@ -0,0 +1,78 @@
+use std::time::Duration;
+
+use actix_web::rt;
+use redis::{AsyncCommands, Client, RedisResult};
+use sea_orm::{
+    ColumnTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter, QuerySelect,
+    TransactionTrait,
+    sea_query::{LockBehavior, LockType},
+};
+
+use super::expire;
+use crate::{
+    entities::{prelude::*, *},
+    live::LiveHub,
+    routes::{DataContainer::LATEST_PREFIX, SensorData::uncache},
+    utils::{env_or, get_redis_id},
+};
+
+/// Starts the background task that expires readings past their container's
+/// `max_instance_age`, which inserts alone would miss on quiet containers.
+pub fn spawn(db: DatabaseConnection, redis: Client, live: LiveHub) {
+    let interval = Duration::from_secs(env_or("CONTAINER_SWEEP_INTERVAL_SECONDS", 60));
+    rt::spawn(async move {
+        let mut interval = rt::time::interval(interval);
+
+        loop {
+            interval.tick().await;
+            if let Err(e) = sweep(&db, &redis, &live).await {
+                eprintln!("Error expiring sensor data: {:?}", e);
+            }
+        }
+    });
+}
+
+/// Each container is expired under a lock on its row, skipped when another
+/// replica's sweeper or a limits update holds it.
+async fn sweep(db: &DatabaseConnection, redis: &Client, live: &LiveHub) -> Result<(), DbErr> {
+    let container_ids: Vec<String> = DataContainer::find()
+        .select_only()
+        .column(data_container::Column::Id)
+        .filter(data_container::Column::MaxInstanceAge.is_not_null())
+        .into_tuple()
+        .all(db)
+        .await?;
+
+    for container_id in container_ids {
+        let txn = db.begin().await?;
+        let Some(container) = DataContainer::find_by_id(&container_id)
+            .lock_with_behavior(LockType::NoKeyUpdate, LockBehavior::SkipLocked)
+            .one(&txn)
+            .await?
+        else {
+            continue;
+        };
+        let (evicted, events) = expire(&txn, &container).await?;
+        txn.commit().await?;
+        live.publish(events).await;
+
+        if !evicted.is_empty()
+            && let Err(e) = uncache_container(redis, &container.id, &evicted).await
+        {
+            eprintln!("Error uncaching expired sensor data: {:?}", e);
+        }
+    }
+    Ok(())
+}
+
+async fn uncache_container(
+    redis: &Client,
+    container_id: &String,
+    ids: &[String],
+) -> RedisResult<()> {
+    let mut redis_conn = redis.get_multiplexed_tokio_connection().await?;
+    uncache(&mut redis_conn, ids).await?;
+    redis_conn
+        .del(get_redis_id(LATEST_PREFIX, container_id))
+        .await
+}
@@ -26,6 +26,8 @@ pub enum Relation {
     Home,
     #[sea_orm(has_many = "super::sensor::Entity")]
     Sensor,
+    #[sea_orm(has_many = "super::subscribers::Entity")]
+    Subscribers,
 }
 
 impl Related<super::home::Entity> for Entity {
@@ -40,4 +42,10 @@ impl Related<super::sensor::Entity> for Entity {
     }
 }
 
+impl Related<super::subscribers::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Subscribers.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
@@ -10,10 +10,22 @@ pub struct Model {
     pub id: String,
     pub sensor_id: String,
     pub create_at: DateTime,
+    pub max_nr_of_instances: Option<i64>,
+    pub max_byte_size: Option<i64>,
+    pub max_instance_age: Option<i64>,
+    pub notify_on_eviction: bool,
+    pub schema: Option<Json>,
+    pub retention_period: Option<i64>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(has_many = "super::alarm::Entity")]
+    Alarm,
+    #[sea_orm(has_many = "super::alarm_rule::Entity")]
+    AlarmRule,
+    #[sea_orm(has_many = "super::alarm_rule_state::Entity")]
+    AlarmRuleState,
     #[sea_orm(
         belongs_to = "super::sensor::Entity",
         from = "Column::SensorId",
@@ -28,6 +40,24 @@ pub enum Relation {
     Subscribers,
 }
 
+impl Related<super::alarm::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Alarm.def()
+    }
+}
+
+impl Related<super::alarm_rule::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::AlarmRule.def()
+    }
+}
+
+impl Related<super::alarm_rule_state::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::AlarmRuleState.def()
+    }
+}
+
 impl Related<super::sensor::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::Sensor.def()
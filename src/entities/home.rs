@@ -17,6 +17,8 @@ pub struct Model {
 pub enum Relation {
     #[sea_orm(has_many = "super::application::Entity")]
     Application,
+    #[sea_orm(has_many = "super::subscribers::Entity")]
+    Subscribers,
 }
 
 impl Related<super::application::Entity> for Entity {
@@ -25,4 +27,10 @@ impl Related<super::application::Entity> for Entity {
     }
 }
 
+impl Related<super::subscribers::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Subscribers.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
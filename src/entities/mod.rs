@@ -2,9 +2,16 @@
 
 pub mod prelude;
 
+pub mod alarm;
+pub mod alarm_rule;
+pub mod alarm_rule_state;
 pub mod application;
 pub mod data_container;
+pub mod dead_letter;
 pub mod home;
+pub mod notification_delivery;
+pub mod notification_queue;
+pub mod sea_orm_active_enums;
 pub mod sensor;
 pub mod sensor_data;
 pub mod subscribers;
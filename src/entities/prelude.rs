@@ -1,8 +1,14 @@
 //! `SeaORM` Entity, @generated by sea-orm-codegen 1.1.9
 
+pub use super::alarm::Entity as Alarm;
+pub use super::alarm_rule::Entity as AlarmRule;
+pub use super::alarm_rule_state::Entity as AlarmRuleState;
 pub use super::application::Entity as Application;
 pub use super::data_container::Entity as DataContainer;
+pub use super::dead_letter::Entity as DeadLetter;
 pub use super::home::Entity as Home;
+pub use super::notification_delivery::Entity as NotificationDelivery;
+pub use super::notification_queue::Entity as NotificationQueue;
 pub use super::sensor::Entity as Sensor;
 pub use super::sensor_data::Entity as SensorData;
 pub use super::subscribers::Entity as Subscribers;
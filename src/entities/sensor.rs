@@ -3,7 +3,7 @@
 use sea_orm::entity::prelude::*;
 use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
 #[sea_orm(table_name = "sensor")]
 pub struct Model {
     #[sea_orm(primary_key, auto_increment = false)]
@@ -12,10 +12,29 @@ pub struct Model {
     #[sea_orm(column_type = "Text")]
     pub name: String,
     pub created_at: DateTime,
+    pub last_seen_at: Option<DateTime>,
+    pub reporting_interval: Option<i64>,
+    pub offline_since: Option<DateTime>,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub kind: Option<String>,
+    pub units: Option<Json>,
+    #[sea_orm(column_type = "Double", nullable)]
+    pub latitude: Option<f64>,
+    #[sea_orm(column_type = "Double", nullable)]
+    pub longitude: Option<f64>,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub manufacturer: Option<String>,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub model_name: Option<String>,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub serial_number: Option<String>,
+    pub tags: Vec<String>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(has_many = "super::alarm_rule::Entity")]
+    AlarmRule,
     #[sea_orm(
         belongs_to = "super::application::Entity",
         from = "Column::ApplicationId",
@@ -26,6 +45,14 @@ pub enum Relation {
     Application,
     #[sea_orm(has_many = "super::data_container::Entity")]
     DataContainer,
+    #[sea_orm(has_many = "super::subscribers::Entity")]
+    Subscribers,
+}
+
+impl Related<super::alarm_rule::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::AlarmRule.def()
+    }
 }
 
 impl Related<super::application::Entity> for Entity {
@@ -40,4 +67,10 @@ impl Related<super::data_container::Entity> for Entity {
     }
 }
 
+impl Related<super::subscribers::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Subscribers.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
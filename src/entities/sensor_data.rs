@@ -11,10 +11,13 @@ pub struct Model {
     pub container_id: String,
     pub created_at: DateTime,
     pub data: Option<Json>,
+    pub measured_at: DateTime,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(has_many = "super::alarm::Entity")]
+    Alarm,
     #[sea_orm(
         belongs_to = "super::data_container::Entity",
         from = "Column::ContainerId",
@@ -23,6 +26,18 @@ pub enum Relation {
         on_delete = "Cascade"
     )]
     DataContainer,
+    #[sea_orm(has_many = "super::dead_letter::Entity")]
+    DeadLetter,
+    #[sea_orm(has_many = "super::notification_delivery::Entity")]
+    NotificationDelivery,
+    #[sea_orm(has_many = "super::notification_queue::Entity")]
+    NotificationQueue,
+}
+
+impl Related<super::alarm::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Alarm.def()
+    }
 }
 
 impl Related<super::data_container::Entity> for Entity {
@@ -31,4 +46,22 @@ impl Related<super::data_container::Entity> for Entity {
     }
 }
 
+impl Related<super::dead_letter::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::DeadLetter.def()
+    }
+}
+
+impl Related<super::notification_delivery::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::NotificationDelivery.def()
+    }
+}
+
+impl Related<super::notification_queue::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::NotificationQueue.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
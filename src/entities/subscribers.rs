@@ -1,5 +1,6 @@
 //! `SeaORM` Entity, @generated by sea-orm-codegen 1.1.9
 
+use super::sea_orm_active_enums::{NotificationContent, SubscriptionStatus};
 use sea_orm::entity::prelude::*;
 use serde::{Deserialize, Serialize};
 
@@ -8,14 +9,32 @@ use serde::{Deserialize, Serialize};
 pub struct Model {
     #[sea_orm(primary_key, auto_increment = false)]
     pub id: String,
-    pub container_id: String,
+    pub home_id: Option<String>,
+    pub application_id: Option<String>,
+    pub sensor_id: Option<String>,
+    pub container_id: Option<String>,
     #[sea_orm(column_type = "Text")]
     pub notification_url: String,
+    pub notification_content: NotificationContent,
+    #[sea_orm(column_type = "Text")]
+    #[serde(skip_serializing, default)]
+    pub secret: String,
+    pub status: SubscriptionStatus,
+    pub criteria: Json,
+    pub last_notified_at: Option<DateTime>,
     pub create_at: DateTime,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::application::Entity",
+        from = "Column::ApplicationId",
+        to = "super::application::Column::Id",
+        on_update = "NoAction",
+        on_delete = "Cascade"
+    )]
+    Application,
     #[sea_orm(
         belongs_to = "super::data_container::Entity",
         from = "Column::ContainerId",
@@ -24,6 +43,34 @@ pub enum Relation {
         on_delete = "Cascade"
     )]
     DataContainer,
+    #[sea_orm(has_many = "super::dead_letter::Entity")]
+    DeadLetter,
+    #[sea_orm(
+        belongs_to = "super::home::Entity",
+        from = "Column::HomeId",
+        to = "super::home::Column::Id",
+        on_update = "NoAction",
+        on_delete = "Cascade"
+    )]
+    Home,
+    #[sea_orm(has_many = "super::notification_delivery::Entity")]
+    NotificationDelivery,
+    #[sea_orm(has_many = "super::notification_queue::Entity")]
+    NotificationQueue,
+    #[sea_orm(
+        belongs_to = "super::sensor::Entity",
+        from = "Column::SensorId",
+        to = "super::sensor::Column::Id",
+        on_update = "NoAction",
+        on_delete = "Cascade"
+    )]
+    Sensor,
+}
+
+impl Related<super::application::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Application.def()
+    }
 }
 
 impl Related<super::data_container::Entity> for Entity {
@@ -32,4 +79,34 @@ impl Related<super::data_container::Entity> for Entity {
     }
 }
 
+impl Related<super::dead_letter::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::DeadLetter.def()
+    }
+}
+
+impl Related<super::home::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Home.def()
+    }
+}
+
+impl Related<super::notification_delivery::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::NotificationDelivery.def()
+    }
+}
+
+impl Related<super::notification_queue::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::NotificationQueue.def()
+    }
+}
+
+impl Related<super::sensor::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Sensor.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
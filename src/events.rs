@@ -0,0 +1,66 @@
+use std::collections::HashMap;
+
+use sea_orm::{ConnectionTrait, DbErr};
+
+use crate::{
+    entities::*,
+    live::LiveEvent,
+    notification::{
+        enqueue, enqueue_batch,
+        envelope::EventType,
+        scope::{Resource, Scope},
+    },
+};
+
+/// Records a change to a resource: queues subscriber notifications on `db`,
+/// which should be the transaction making the change, and returns the live
+/// event to publish once that transaction has committed.
+pub async fn record<C: ConnectionTrait>(
+    db: &C,
+    event_type: EventType,
+    resource: Resource<'_>,
+) -> Result<Option<LiveEvent>, DbErr> {
+    let scope = match resource {
+        Resource::Sensor(entity) => Scope::of_sensor(db, entity).await?,
+        Resource::DataContainer(entity) => Scope::of_data_container(db, entity).await?,
+        Resource::SensorData(entity) => Scope::of_sensor_data(db, entity).await?,
+        Resource::Alarm(entity) => Scope::of_alarm(db, entity).await?,
+    };
+    let Some(scope) = scope else {
+        return Ok(None);
+    };
+
+    enqueue(db, event_type, &scope, resource).await?;
+    Ok(Some(LiveEvent::new(event_type, scope, resource)))
+}
+
+/// Records readings stored together: one coalesced notification per
+/// subscriber, but a live event per reading.
+pub async fn record_batch<C: ConnectionTrait>(
+    db: &C,
+    readings: &[sensor_data::Model],
+) -> Result<Vec<LiveEvent>, DbErr> {
+    let mut scopes: HashMap<&str, Option<Scope>> = HashMap::new();
+    for entity in readings {
+        if !scopes.contains_key(entity.container_id.as_str()) {
+            let scope = Scope::of_sensor_data(db, entity).await?;
+            scopes.insert(&entity.container_id, scope);
+        }
+    }
+    let scoped: Vec<(&Scope, &sensor_data::Model)> = readings
+        .iter()
+        .filter_map(|entity| Some((scopes[entity.container_id.as_str()].as_ref()?, entity)))
+        .collect();
+
+    enqueue_batch(db, &scoped).await?;
+    Ok(scoped
+        .into_iter()
+        .map(|(scope, entity)| {
+            LiveEvent::new(
+                EventType::SensorDataCreated,
+                scope.clone(),
+                Resource::SensorData(entity),
+            )
+        })
+        .collect())
+}
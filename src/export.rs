@@ -0,0 +1,233 @@
+//! CSV and NDJSON export of a container's readings, streamed row by row from
+//! the database so a long history is never held in memory.
+
+use actix_web::{
+    Error, HttpRequest, error::ErrorInternalServerError, http::header, rt, web::Bytes,
+};
+use futures_util::{Stream, StreamExt, pin_mut, stream};
+use sea_orm::{ConnectionTrait, DatabaseConnection, DbErr, QueryTrait, Select, Statement};
+use serde_json::Value;
+use tokio::sync::mpsc;
+
+use crate::entities::{prelude::*, *};
+
+/// Rows buffered between the database and a slow client.
+const BUFFER_SIZE: usize = 256;
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.f";
+/// The reading's own columns, ahead of the `data` keys.
+const READING_FIELDS: [&str; 3] = ["id", "created_at", "measured_at"];
+
+#[derive(Clone, Copy)]
+pub enum Format {
+    Csv,
+    Ndjson,
+}
+
+impl Format {
+    /// The export format named in the `Accept` header, if any.
+    pub fn from_accept(req: &HttpRequest) -> Option<Self> {
+        let accept = req.headers().get(header::ACCEPT)?.to_str().ok()?;
+        accept
+            .split(',')
+            .map(|media_type| media_type.split(';').next().unwrap_or("").trim())
+            .find_map(Format::from_media_type)
+    }
+
+    /// The format of an import body, from its `Content-Type` header.
+    pub fn from_content_type(req: &HttpRequest) -> Option<Self> {
+        let content_type = req.headers().get(header::CONTENT_TYPE)?.to_str().ok()?;
+        Format::from_media_type(content_type.split(';').next().unwrap_or("").trim())
+    }
+
+    fn from_media_type(media_type: &str) -> Option<Self> {
+        match media_type {
+            "text/csv" => Some(Format::Csv),
+            "application/x-ndjson" => Some(Format::Ndjson),
+            _ => None,
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Format::Csv => "text/csv; charset=utf-8",
+            Format::Ndjson => "application/x-ndjson",
+        }
+    }
+}
+
+/// Sorted top-level keys of the `data` objects of the rows `select`
+/// exports, limit included, which become the CSV columns after the reading's
+/// own fields.
+pub async fn data_columns<C: ConnectionTrait>(
+    db: &C,
+    select: Select<SensorData>,
+) -> Result<Vec<String>, DbErr> {
+    let backend = db.get_database_backend();
+    let exported = select.build(backend);
+    let rows = db
+        .query_all(Statement::from_sql_and_values(
+            backend,
+            format!(
+                "SELECT DISTINCT jsonb_object_keys(data::jsonb) AS key FROM ({}) AS exported \
+                 WHERE jsonb_typeof(data::jsonb) = 'object'",
+                exported.sql
+            ),
+            exported.values.map(|values| values.0).unwrap_or_default(),
+        ))
+        .await?;
+    let mut columns = rows
+        .iter()
+        .map(|row| row.try_get::<String>("", "key"))
+        .collect::<Result<Vec<_>, _>>()?;
+    columns.sort();
+    Ok(columns)
+}
+
+/// Streams the rows of `select` encoded as `format`. `columns` is only used
+/// for CSV.
+pub fn export_stream(
+    db: DatabaseConnection,
+    select: Select<SensorData>,
+    format: Format,
+    columns: Vec<String>,
+) -> impl Stream<Item = Result<Bytes, Error>> {
+    let (sender, receiver) = mpsc::channel(BUFFER_SIZE);
+    rt::spawn(async move {
+        let rows = match select.stream(&db).await {
+            Ok(rows) => rows,
+            Err(e) => {
+                let _ = sender.send(Err(e)).await;
+                return;
+            }
+        };
+        pin_mut!(rows);
+        while let Some(row) = rows.next().await {
+            // The client hung up.
+            if sender.send(row).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let head = match format {
+        Format::Csv => Some(csv_header(&columns)),
+        Format::Ndjson => None,
+    };
+    let rows = stream::unfold(receiver, |mut receiver| async move {
+        let row = receiver.recv().await?;
+        Some((row, receiver))
+    })
+    .map(move |row| match row {
+        Ok(entity) => Ok(match format {
+            Format::Csv => csv_row(&entity, &columns),
+            Format::Ndjson => {
+                let mut line = serde_json::to_vec(&entity).unwrap();
+                line.push(b'\n');
+                Bytes::from(line)
+            }
+        }),
+        Err(e) => {
+            eprintln!("Error exporting sensor data: {:?}", e);
+            Err(ErrorInternalServerError("Query failed"))
+        }
+    });
+    stream::iter(head.map(Ok)).chain(rows)
+}
+
+fn csv_header(columns: &[String]) -> Bytes {
+    let fields = READING_FIELDS
+        .into_iter()
+        .map(escape)
+        .chain(columns.iter().map(|column| escape(&header(column))));
+    csv_line(fields)
+}
+
+/// The header of a `data` key. Keys named like one of the reading's own
+/// fields are prefixed with `data.` so no two columns share a header.
+fn header(column: &str) -> String {
+    if READING_FIELDS.contains(&column) {
+        format!("data.{}", column)
+    } else {
+        column.to_owned()
+    }
+}
+
+fn csv_row(entity: &sensor_data::Model, columns: &[String]) -> Bytes {
+    let data = entity.data.as_ref();
+    let fields = [
+        escape(&entity.id),
+        entity.created_at.format(TIMESTAMP_FORMAT).to_string(),
+        entity.measured_at.format(TIMESTAMP_FORMAT).to_string(),
+    ]
+    .into_iter()
+    .chain(columns.iter().map(|column| {
+        match data.and_then(|data| data.get(column)) {
+            None | Some(Value::Null) => String::new(),
+            Some(Value::String(value)) => escape(value),
+            // Nested objects and arrays stay JSON within their cell.
+            Some(value) => escape(&value.to_string()),
+        }
+    }));
+    csv_line(fields)
+}
+
+fn csv_line(fields: impl Iterator<Item = String>) -> Bytes {
+    let mut line = fields.collect::<Vec<_>>().join(",");
+    line.push_str("\r\n");
+    Bytes::from(line)
+}
+
+/// Quotes a field per RFC 4180 when it needs it.
+fn escape(field: &str) -> String {
+    if field.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn escapes_only_when_needed() {
+        assert_eq!(escape("plain"), "plain");
+        assert_eq!(escape(""), "");
+        assert_eq!(escape("a,b"), "\"a,b\"");
+        assert_eq!(escape("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(escape("two\nlines"), "\"two\nlines\"");
+        assert_eq!(escape("cr\r"), "\"cr\r\"");
+    }
+
+    #[test]
+    fn prefixes_keys_named_like_reading_fields() {
+        let columns = ["id".to_owned(), "temperature".to_owned()];
+        assert_eq!(
+            csv_header(&columns),
+            "id,created_at,measured_at,data.id,temperature\r\n"
+        );
+    }
+
+    #[test]
+    fn writes_data_keys_as_cells() {
+        let at = DateTime::from_timestamp_millis(1735787045678)
+            .unwrap()
+            .naive_utc();
+        let entity = sensor_data::Model {
+            id: "r1".to_owned(),
+            container_id: "c1".to_owned(),
+            created_at: at,
+            measured_at: at,
+            data: Some(json!({ "id": 7, "note": "a,b", "gps": { "lat": 1.5 }, "gone": null })),
+        };
+        let columns = ["gone", "gps", "id", "missing", "note"].map(str::to_owned);
+        assert_eq!(
+            csv_row(&entity, &columns),
+            "r1,2025-01-02T03:04:05.678,2025-01-02T03:04:05.678,,\"{\"\"lat\"\":1.5}\",7,,\"a,b\"\r\n"
+        );
+    }
+}
@@ -0,0 +1,451 @@
+//! Predicates over the `data` payload of sensor readings, written as
+//! `<path> <operator> <value>`, e.g. `temperature > 30` or `gps.fix == true`.
+//! A [`DataFilter`] joins them with `and`, adds `exists(<path>)` and
+//! `!exists(<path>)`, and compiles to JSONB operators for the list endpoints.
+
+use std::{cmp::Ordering, fmt, str::FromStr};
+
+use sea_orm::{
+    Condition as SqlCondition,
+    sea_query::{Expr, SimpleExpr},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Operator {
+    Eq,
+    Ne,
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+}
+
+impl Operator {
+    /// Two-character operators come first so `>=` is not read as `>`.
+    const ALL: [Operator; 6] = [
+        Operator::Gte,
+        Operator::Lte,
+        Operator::Eq,
+        Operator::Ne,
+        Operator::Gt,
+        Operator::Lt,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Operator::Eq => "==",
+            Operator::Ne => "!=",
+            Operator::Gt => ">",
+            Operator::Gte => ">=",
+            Operator::Lt => "<",
+            Operator::Lte => "<=",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Condition {
+    pub path: Vec<String>,
+    pub operator: Operator,
+    pub value: Value,
+}
+
+impl Condition {
+    pub fn matches(&self, data: &Value) -> bool {
+        let Some(actual) = lookup(data, &self.path) else {
+            return false;
+        };
+        match self.operator {
+            Operator::Eq => equal(actual, &self.value),
+            Operator::Ne => !equal(actual, &self.value),
+            Operator::Gt => compare(actual, &self.value) == Some(Ordering::Greater),
+            Operator::Gte => matches!(
+                compare(actual, &self.value),
+                Some(Ordering::Greater | Ordering::Equal)
+            ),
+            Operator::Lt => compare(actual, &self.value) == Some(Ordering::Less),
+            Operator::Lte => matches!(
+                compare(actual, &self.value),
+                Some(Ordering::Less | Ordering::Equal)
+            ),
+        }
+    }
+
+    /// SQL equivalent of [`Self::matches`] on `sensor_data.data`. The `CASE`
+    /// keeps Postgres from casting values of another type, which would fail.
+    pub fn to_sql(&self) -> SimpleExpr {
+        let path = || sea_orm::Value::from(self.path.to_owned());
+        match (self.operator, &self.value) {
+            (Operator::Eq | Operator::Ne, value) => Expr::cust_with_values(
+                format!(
+                    "(data::jsonb #> $1) {} $2::jsonb",
+                    sql_operator(self.operator)
+                ),
+                [path(), value.to_string().into()],
+            ),
+            (operator, Value::Number(number)) => Expr::cust_with_values(
+                format!(
+                    "CASE WHEN jsonb_typeof(data::jsonb #> $1) = 'number' \
+                     THEN (data::jsonb #>> $1)::float8 {} $2 END",
+                    sql_operator(operator)
+                ),
+                [path(), number.as_f64().unwrap_or(f64::NAN).into()],
+            ),
+            (operator, Value::String(text)) => Expr::cust_with_values(
+                format!(
+                    "CASE WHEN jsonb_typeof(data::jsonb #> $1) = 'string' \
+                     THEN (data::jsonb #>> $1) COLLATE \"C\" {} $2 END",
+                    sql_operator(operator)
+                ),
+                [path(), text.to_owned().into()],
+            ),
+            // Rejected when parsing a `DataFilter`; `matches` never holds.
+            _ => Expr::cust("FALSE"),
+        }
+    }
+}
+
+fn sql_operator(operator: Operator) -> &'static str {
+    match operator {
+        Operator::Eq => "=",
+        Operator::Ne => "<>",
+        operator => operator.as_str(),
+    }
+}
+
+impl FromStr for Condition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (index, operator) = s
+            .char_indices()
+            .find_map(|(index, _)| {
+                Operator::ALL
+                    .into_iter()
+                    .find(|operator| s[index..].starts_with(operator.as_str()))
+                    .map(|operator| (index, operator))
+            })
+            .ok_or_else(|| format!("Missing operator in condition `{}`", s))?;
+
+        let path = parse_path(&s[..index])?;
+        let raw_value = s[index + operator.as_str().len()..].trim();
+        if raw_value.is_empty() {
+            return Err(format!("Missing value in condition `{}`", s));
+        }
+        // Bare words are taken as strings so `status == ok` works unquoted.
+        let value =
+            serde_json::from_str(raw_value).unwrap_or_else(|_| Value::String(raw_value.to_owned()));
+
+        Ok(Self {
+            path,
+            operator,
+            value,
+        })
+    }
+}
+
+impl TryFrom<String> for Condition {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<Condition> for String {
+    fn from(condition: Condition) -> Self {
+        condition.to_string()
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {}",
+            self.path.join("."),
+            self.operator.as_str(),
+            self.value
+        )
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Predicate {
+    Compare(Condition),
+    Exists(Vec<String>),
+    Missing(Vec<String>),
+}
+
+impl Predicate {
+    fn to_sql(&self) -> SimpleExpr {
+        match self {
+            Predicate::Compare(condition) => condition.to_sql(),
+            Predicate::Exists(path) => {
+                Expr::cust_with_values("(data::jsonb #> $1) IS NOT NULL", [path.to_owned()])
+            }
+            Predicate::Missing(path) => {
+                Expr::cust_with_values("(data::jsonb #> $1) IS NULL", [path.to_owned()])
+            }
+        }
+    }
+}
+
+impl FromStr for Predicate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let exists = |prefix: &str| {
+            s.strip_prefix(prefix)
+                .and_then(|rest| rest.strip_suffix(')'))
+                .map(parse_path)
+        };
+        if let Some(path) = exists("!exists(") {
+            return Ok(Predicate::Missing(path?));
+        }
+        if let Some(path) = exists("exists(") {
+            return Ok(Predicate::Exists(path?));
+        }
+
+        let condition: Condition = s.parse()?;
+        let ordered = !matches!(condition.operator, Operator::Eq | Operator::Ne);
+        if ordered && !matches!(condition.value, Value::Number(_) | Value::String(_)) {
+            return Err(format!(
+                "`{}` needs a number or a string in `{}`",
+                condition.operator.as_str(),
+                s
+            ));
+        }
+        Ok(Predicate::Compare(condition))
+    }
+}
+
+/// Query string filter on readings' `data`, e.g.
+/// `battery < 20 and exists(gps.lat) and status != "ok"`.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct DataFilter(pub Vec<Predicate>);
+
+impl DataFilter {
+    pub fn to_sql(&self) -> SqlCondition {
+        self.0
+            .iter()
+            .fold(SqlCondition::all(), |condition, predicate| {
+                condition.add(predicate.to_sql())
+            })
+    }
+}
+
+impl FromStr for DataFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        split_and(s)
+            .into_iter()
+            .map(str::parse)
+            .collect::<Result<_, _>>()
+            .map(DataFilter)
+    }
+}
+
+impl TryFrom<String> for DataFilter {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+/// Splits on the word `and` outside of double-quoted values.
+fn split_and(s: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let (mut start, mut quoted, mut escaped) = (0, false, false);
+    for (index, c) in s.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                let rest = &s[index + c.len_utf8()..];
+                let is_and = rest
+                    .get(..3)
+                    .is_some_and(|word| word.eq_ignore_ascii_case("and"))
+                    && rest[3..].starts_with(char::is_whitespace);
+                if is_and {
+                    parts.push(&s[start..index]);
+                    start = index + c.len_utf8() + 3;
+                }
+            }
+            _ => {}
+        }
+    }
+    parts.push(&s[start..]);
+    parts
+}
+
+/// Splits a dotted path such as `$.gps.lat` or `readings.0` into its keys.
+pub fn parse_path(raw: &str) -> Result<Vec<String>, String> {
+    let raw = raw.trim();
+    let raw = raw
+        .strip_prefix("$.")
+        .or_else(|| raw.strip_prefix('$'))
+        .unwrap_or(raw);
+    let path: Vec<String> = raw.split('.').map(|key| key.trim().to_owned()).collect();
+    if path.iter().any(String::is_empty) {
+        return Err(format!("Invalid path `{}`", raw));
+    }
+    Ok(path)
+}
+
+pub fn lookup<'a>(data: &'a Value, path: &[String]) -> Option<&'a Value> {
+    path.iter().try_fold(data, |value, key| match value {
+        Value::Array(items) => key.parse::<usize>().ok().and_then(|index| items.get(index)),
+        _ => value.get(key.as_str()),
+    })
+}
+
+fn equal(actual: &Value, expected: &Value) -> bool {
+    match (actual.as_f64(), expected.as_f64()) {
+        (Some(actual), Some(expected)) => actual == expected,
+        _ => actual == expected,
+    }
+}
+
+fn compare(actual: &Value, expected: &Value) -> Option<Ordering> {
+    match (actual, expected) {
+        (Value::Number(actual), Value::Number(expected)) => {
+            actual.as_f64()?.partial_cmp(&expected.as_f64()?)
+        }
+        (Value::String(actual), Value::String(expected)) => Some(actual.cmp(expected)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sea_orm::{DbBackend, EntityTrait, QueryFilter, QueryTrait};
+    use serde_json::json;
+
+    use super::*;
+    use crate::entities::prelude::SensorData;
+
+    fn condition(s: &str) -> Condition {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_conditions() {
+        assert_eq!(
+            condition("temperature >= 30"),
+            Condition {
+                path: vec!["temperature".to_owned()],
+                operator: Operator::Gte,
+                value: json!(30),
+            }
+        );
+        assert_eq!(condition("$.gps.fix == true").path, ["gps", "fix"]);
+        assert_eq!(condition("status != ok").value, json!("ok"));
+        assert_eq!(condition("status == \"a b\"").value, json!("a b"));
+    }
+
+    #[test]
+    fn rejects_incomplete_conditions() {
+        assert!("temperature".parse::<Condition>().is_err());
+        assert!("temperature >".parse::<Condition>().is_err());
+        assert!("> 30".parse::<Condition>().is_err());
+        assert!("gps..lat == 1".parse::<Condition>().is_err());
+    }
+
+    #[test]
+    fn round_trips_through_strings() {
+        let parsed = condition("gps.speed < 12.5");
+        assert_eq!(condition(&parsed.to_string()), parsed);
+    }
+
+    #[test]
+    fn matches_numbers_strings_and_paths() {
+        let data = json!({ "temperature": 31, "status": "ok", "readings": [1, 2] });
+        assert!(condition("temperature > 30").matches(&data));
+        assert!(condition("temperature == 31.0").matches(&data));
+        assert!(!condition("temperature < 30").matches(&data));
+        assert!(condition("status == ok").matches(&data));
+        assert!(condition("status < pk").matches(&data));
+        assert!(condition("readings.1 == 2").matches(&data));
+        // Values of another type or missing paths never compare.
+        assert!(!condition("status > 3").matches(&data));
+        assert!(!condition("humidity != 3").matches(&data));
+    }
+
+    fn split(s: &str) -> Vec<&str> {
+        split_and(s).into_iter().map(str::trim).collect()
+    }
+
+    #[test]
+    fn splits_on_and_outside_quotes() {
+        assert_eq!(
+            split("a > 1 and b < 2 AND exists(c)"),
+            ["a > 1", "b < 2", "exists(c)"]
+        );
+        assert_eq!(
+            split(r#"name == "salt and pepper" and x == 1"#),
+            [r#"name == "salt and pepper""#, "x == 1"]
+        );
+        assert_eq!(
+            split(r#"name == "say \"and\" here""#),
+            [r#"name == "say \"and\" here""#]
+        );
+        // `and` inside a word is not a separator.
+        assert_eq!(split("brand == x"), ["brand == x"]);
+    }
+
+    #[test]
+    fn parses_filters() {
+        let filter: DataFilter = "battery < 20 and exists(gps.lat) and !exists(error)"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            filter.0,
+            [
+                Predicate::Compare(condition("battery < 20")),
+                Predicate::Exists(vec!["gps".to_owned(), "lat".to_owned()]),
+                Predicate::Missing(vec!["error".to_owned()]),
+            ]
+        );
+        assert!("battery < true".parse::<DataFilter>().is_err());
+        assert!("battery < 20 and ".parse::<DataFilter>().is_err());
+    }
+
+    fn sql(filter: &str) -> String {
+        let filter: DataFilter = filter.parse().unwrap();
+        SensorData::find()
+            .filter(filter.to_sql())
+            .build(DbBackend::Postgres)
+            .to_string()
+    }
+
+    #[test]
+    fn compiles_to_jsonb_operators() {
+        let numeric = sql("gps.speed > 12.5");
+        assert!(numeric.contains(
+            "CASE WHEN jsonb_typeof(data::jsonb #> ARRAY ['gps','speed']) = 'number' \
+             THEN (data::jsonb #>> ARRAY ['gps','speed'])::float8 > 12.5 END"
+        ));
+
+        let text = sql("status <= ok");
+        assert!(text.contains(
+            "CASE WHEN jsonb_typeof(data::jsonb #> ARRAY ['status']) = 'string' \
+             THEN (data::jsonb #>> ARRAY ['status']) COLLATE \"C\" <= 'ok' END"
+        ));
+
+        let combined = sql("status != ok and exists(a) and !exists(b)");
+        assert!(combined.contains(
+            "((data::jsonb #> ARRAY ['status']) <> E'\\\"ok\\\"'::jsonb) \
+             AND ((data::jsonb #> ARRAY ['a']) IS NOT NULL) \
+             AND ((data::jsonb #> ARRAY ['b']) IS NULL)"
+        ));
+    }
+}
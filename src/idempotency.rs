@@ -0,0 +1,83 @@
+//! Deduplication of retried ingestion requests. A reading's `message_id` (or
+//! `Idempotency-Key` header) is remembered per container in Redis for
+//! `IDEMPOTENCY_WINDOW_SECONDS`, mapping it to the row it created. While the
+//! first request is being stored the id is only held for
+//! `IDEMPOTENCY_PENDING_SECONDS`, so a crash in between blocks retries briefly.
+
+use std::sync::LazyLock;
+
+use redis::{AsyncCommands, RedisResult, SetExpiry, SetOptions, aio::MultiplexedConnection};
+
+use crate::utils::{env_or, get_redis_id};
+
+const PREFIX: &str = "SensorDataMessage";
+/// Held while the first request with a message id is being stored.
+const PENDING: &str = "pending";
+
+static WINDOW_SECONDS: LazyLock<u64> =
+    LazyLock::new(|| env_or("IDEMPOTENCY_WINDOW_SECONDS", 86400));
+static PENDING_SECONDS: LazyLock<u64> = LazyLock::new(|| env_or("IDEMPOTENCY_PENDING_SECONDS", 60));
+
+pub enum Claim {
+    /// First time the message id is seen; store the reading, then call
+    /// [`complete`] or, if that fails, [`release`].
+    New,
+    /// Already stored as the sensor data row with this id.
+    Replay(String),
+    /// Another request with the same message id is still being stored.
+    InFlight,
+}
+
+fn key(container_id: &str, message_id: &str) -> String {
+    get_redis_id(PREFIX, &format!("{}_{}", container_id, message_id))
+}
+
+pub async fn claim(
+    redis_conn: &mut MultiplexedConnection,
+    container_id: &str,
+    message_id: &str,
+) -> RedisResult<Claim> {
+    let key = key(container_id, message_id);
+    let claimed: Option<String> = redis_conn
+        .set_options(
+            &key,
+            PENDING,
+            SetOptions::default()
+                .with_expiration(SetExpiry::EX(*PENDING_SECONDS))
+                .conditional_set(redis::ExistenceCheck::NX),
+        )
+        .await?;
+    if claimed.is_some() {
+        return Ok(Claim::New);
+    }
+
+    let existing: Option<String> = redis_conn.get(&key).await?;
+    Ok(match existing {
+        Some(id) if id != PENDING => Claim::Replay(id),
+        // `None` means the key expired between both commands; a retry wins.
+        _ => Claim::InFlight,
+    })
+}
+
+pub async fn complete(
+    redis_conn: &mut MultiplexedConnection,
+    container_id: &str,
+    message_id: &str,
+    sensor_data_id: &str,
+) -> RedisResult<()> {
+    redis_conn
+        .set_options(
+            key(container_id, message_id),
+            sensor_data_id,
+            SetOptions::default().with_expiration(SetExpiry::EX(*WINDOW_SECONDS)),
+        )
+        .await
+}
+
+pub async fn release(
+    redis_conn: &mut MultiplexedConnection,
+    container_id: &str,
+    message_id: &str,
+) -> RedisResult<()> {
+    redis_conn.del(key(container_id, message_id)).await
+}
@@ -0,0 +1,81 @@
+use std::{fs, io, path::Path};
+
+use sea_orm::{DatabaseConnection, EntityTrait};
+
+use super::{ImportError, Mapping, parse, store};
+use crate::{entities::prelude::*, export::Format};
+
+const USAGE: &str = "Usage: import <container id> <file> --timestamp <column> \
+    [--fields <column[:path],...>] [--format csv|ndjson]";
+
+/// `import` command: the import endpoint for files on the server, e.g.
+/// `M2MSystem import <container id> history.csv --timestamp time`. The format
+/// defaults to the file's extension.
+pub async fn run(db: &DatabaseConnection, args: &[String]) -> io::Result<()> {
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidInput, message);
+
+    let mut positional = Vec::new();
+    let (mut timestamp, mut fields, mut format) = (None, None, None);
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let slot = match arg.as_str() {
+            "--timestamp" => &mut timestamp,
+            "--fields" => &mut fields,
+            "--format" => &mut format,
+            flag if flag.starts_with("--") => {
+                return Err(invalid(format!("Unknown option `{}`\n{}", flag, USAGE)));
+            }
+            _ => {
+                positional.push(arg);
+                continue;
+            }
+        };
+        *slot = Some(
+            args.next()
+                .ok_or_else(|| invalid(format!("Missing value for `{}`\n{}", arg, USAGE)))?,
+        );
+    }
+    let ([container_id, file], Some(timestamp)) = (positional.as_slice(), timestamp) else {
+        return Err(invalid(USAGE.to_owned()));
+    };
+
+    let format = match format
+        .map(String::as_str)
+        .or_else(|| Path::new(file).extension()?.to_str())
+    {
+        Some("csv") => Format::Csv,
+        Some("ndjson" | "jsonl") => Format::Ndjson,
+        _ => return Err(invalid("Cannot tell the format, pass --format".to_owned())),
+    };
+    let mapping = Mapping::parse(timestamp, fields.map(String::as_str)).map_err(invalid)?;
+
+    let container = DataContainer::find_by_id(container_id.as_str())
+        .one(db)
+        .await
+        .map_err(io::Error::other)?
+        .ok_or_else(|| invalid(format!("Data container `{}` not found", container_id)))?;
+    let input = fs::read(file)?;
+
+    let readings = match parse(&input, format, &mapping, &container) {
+        Ok(readings) => readings,
+        Err(ImportError::Invalid(error)) => return Err(invalid(error)),
+        Err(ImportError::Rows(rows)) => {
+            for row in &rows {
+                eprintln!("line {}: {}", row.line, row.error);
+                for violation in &row.violations {
+                    eprintln!("  {}: {}", violation.path, violation.message);
+                }
+            }
+            return Err(invalid(format!(
+                "Import rejected, nothing was stored ({} invalid rows shown)",
+                rows.len()
+            )));
+        }
+    };
+
+    let imported = store(db, &container.id, readings)
+        .await
+        .map_err(io::Error::other)?;
+    println!("Imported {} readings into {}", imported, container.id);
+    Ok(())
+}
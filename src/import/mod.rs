@@ -0,0 +1,421 @@
+//! Backfill of historical readings from CSV or NDJSON, e.g. when devices move
+//! over from another platform. Imports are all or nothing and, unlike
+//! ingestion, queue no notifications and publish no live events.
+
+use actix_web::{
+    Error, HttpResponse,
+    error::{ErrorBadRequest, InternalError},
+};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use jsonschema::Validator;
+use nanoid::nanoid;
+use sea_orm::{DbErr, EntityTrait, TransactionTrait};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::{
+    entities::{prelude::*, *},
+    export::Format,
+    filter::{lookup, parse_path},
+    schema::{self, Violation},
+    utils::parse_timestamp,
+};
+
+pub mod cli;
+
+const REJECTION: &str = "Import rejected";
+/// Rows per INSERT; the whole import still shares one transaction.
+const CHUNK_SIZE: usize = 1000;
+/// Invalid rows reported before parsing gives up.
+const MAX_REPORTED_ERRORS: usize = 100;
+
+/// `timestamp` names the column holding when each reading was taken.
+/// `fields` maps columns to `data` fields as `column` or `column:path`, comma
+/// separated, e.g. `temp:climate.temperature,humidity`; without it every
+/// other column becomes a top-level field of the same name.
+#[derive(Deserialize)]
+pub struct ImportQuery {
+    pub timestamp: String,
+    pub fields: Option<String>,
+}
+
+pub struct Mapping {
+    timestamp: String,
+    fields: Option<Vec<(String, Vec<String>)>>,
+}
+
+impl Mapping {
+    pub fn parse(timestamp: &str, fields: Option<&str>) -> Result<Self, String> {
+        let fields = fields
+            .map(|fields| {
+                fields
+                    .split(',')
+                    .map(|field| {
+                        let (column, path) = field.split_once(':').unwrap_or((field, field));
+                        let column = column.trim();
+                        if column.is_empty() {
+                            return Err(format!("Invalid field mapping `{}`", field));
+                        }
+                        Ok((column.to_owned(), parse_path(path)?))
+                    })
+                    .collect::<Result<Vec<_>, String>>()
+            })
+            .transpose()?;
+        Ok(Self {
+            timestamp: timestamp.trim().to_owned(),
+            fields,
+        })
+    }
+}
+
+#[derive(Serialize)]
+pub struct RowError {
+    /// 1-based line of the input the row starts on.
+    pub line: u64,
+    pub error: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub violations: Vec<Violation>,
+}
+
+#[derive(Serialize)]
+struct Rejection<'a> {
+    error: &'static str,
+    rows: &'a [RowError],
+}
+
+#[derive(Serialize)]
+pub struct ImportSummary {
+    pub imported: usize,
+}
+
+/// A reading ready to store: when it was measured and its `data`.
+pub struct Reading {
+    pub measured_at: NaiveDateTime,
+    pub data: Value,
+}
+
+pub enum ImportError {
+    /// The input as a whole is unusable, e.g. a mapped column is missing.
+    Invalid(String),
+    Rows(Vec<RowError>),
+}
+
+impl ImportError {
+    pub fn into_response(self) -> Error {
+        match self {
+            ImportError::Invalid(error) => ErrorBadRequest(error),
+            ImportError::Rows(rows) => {
+                let response = HttpResponse::BadRequest().json(Rejection {
+                    error: REJECTION,
+                    rows: &rows,
+                });
+                InternalError::from_response(REJECTION, response).into()
+            }
+        }
+    }
+}
+
+/// Parses and validates every row against the mapping and the container's
+/// schema. Any invalid row rejects the whole input.
+pub fn parse(
+    input: &[u8],
+    format: Format,
+    mapping: &Mapping,
+    container: &data_container::Model,
+) -> Result<Vec<Reading>, ImportError> {
+    let validator = container.schema.as_ref().and_then(schema::compile);
+    let mut readings = Vec::new();
+    let mut errors = Vec::new();
+
+    let mut accept = |line: u64, row: Result<Reading, String>| {
+        match check(validator.as_ref(), line, row) {
+            Ok(reading) => readings.push(reading),
+            Err(error) => errors.push(error),
+        }
+        errors.len() < MAX_REPORTED_ERRORS
+    };
+
+    match format {
+        Format::Csv => {
+            let mut reader = csv::ReaderBuilder::new()
+                .trim(csv::Trim::All)
+                .from_reader(input);
+            let headers = reader
+                .headers()
+                .map_err(|e| ImportError::Invalid(format!("Invalid CSV header: {}", e)))?
+                .clone();
+            let column = |name: &str| {
+                headers
+                    .iter()
+                    .position(|header| header == name)
+                    .ok_or_else(|| ImportError::Invalid(format!("Missing column `{}`", name)))
+            };
+            let timestamp = column(&mapping.timestamp)?;
+            let fields: Vec<(usize, Vec<String>)> = match &mapping.fields {
+                Some(fields) => fields
+                    .iter()
+                    .map(|(name, path)| Ok((column(name)?, path.to_owned())))
+                    .collect::<Result<_, ImportError>>()?,
+                None => headers
+                    .iter()
+                    .enumerate()
+                    .filter(|(index, _)| *index != timestamp)
+                    .map(|(index, name)| (index, vec![name.to_owned()]))
+                    .collect(),
+            };
+
+            for record in reader.records() {
+                let (line, row) = match record {
+                    Ok(record) => {
+                        let line = record.position().map_or(0, |position| position.line());
+                        (line, csv_reading(&record, timestamp, &fields))
+                    }
+                    Err(e) => (
+                        e.position().map_or(0, |position| position.line()),
+                        Err(format!("Invalid CSV row: {}", e)),
+                    ),
+                };
+                if !accept(line, row) {
+                    break;
+                }
+            }
+        }
+        Format::Ndjson => {
+            let timestamp = parse_path(&mapping.timestamp).map_err(ImportError::Invalid)?;
+            for (index, line) in input.split(|byte| *byte == b'\n').enumerate() {
+                if line.iter().all(u8::is_ascii_whitespace) {
+                    continue;
+                }
+                let row = serde_json::from_slice::<Value>(line)
+                    .map_err(|e| format!("Invalid JSON: {}", e))
+                    .and_then(|object| json_reading(&object, &timestamp, mapping));
+                if !accept(index as u64 + 1, row) {
+                    break;
+                }
+            }
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(readings)
+    } else {
+        Err(ImportError::Rows(errors))
+    }
+}
+
+fn check(
+    validator: Option<&Validator>,
+    line: u64,
+    row: Result<Reading, String>,
+) -> Result<Reading, RowError> {
+    let reading = row.map_err(|error| RowError {
+        line,
+        error,
+        violations: Vec::new(),
+    })?;
+    if let Some(validator) = validator {
+        schema::validate_with(validator, &reading.data).map_err(|violations| RowError {
+            line,
+            error: schema::REJECTION.to_owned(),
+            violations,
+        })?;
+    }
+    Ok(reading)
+}
+
+fn csv_reading(
+    record: &csv::StringRecord,
+    timestamp: usize,
+    fields: &[(usize, Vec<String>)],
+) -> Result<Reading, String> {
+    let raw = record.get(timestamp).unwrap_or("");
+    let measured_at = parse_timestamp(raw)
+        .ok_or_else(|| format!("Invalid timestamp `{}`", raw))?
+        .naive_utc();
+
+    let mut data = Map::new();
+    for (index, path) in fields {
+        let cell = record.get(*index).unwrap_or("");
+        if cell.is_empty() {
+            continue;
+        }
+        // Like filter values, cells that are not JSON are taken as strings.
+        let value = serde_json::from_str(cell).unwrap_or_else(|_| Value::String(cell.to_owned()));
+        set(&mut data, path, value)?;
+    }
+    Ok(Reading {
+        measured_at,
+        data: Value::Object(data),
+    })
+}
+
+fn json_reading(
+    object: &Value,
+    timestamp: &[String],
+    mapping: &Mapping,
+) -> Result<Reading, String> {
+    if !object.is_object() {
+        return Err("Expected a JSON object".to_owned());
+    }
+    let measured_at = match lookup(object, timestamp) {
+        Some(Value::Number(millis)) => millis.as_i64().and_then(DateTime::from_timestamp_millis),
+        Some(Value::String(raw)) => parse_timestamp(raw),
+        _ => None,
+    }
+    .ok_or_else(|| format!("Missing or invalid `{}`", mapping.timestamp))?
+    .naive_utc();
+
+    let data = match &mapping.fields {
+        Some(fields) => {
+            let mut data = Map::new();
+            for (column, path) in fields {
+                let source = parse_path(column)?;
+                if let Some(value) = lookup(object, &source).filter(|value| !value.is_null()) {
+                    set(&mut data, path, value.to_owned())?;
+                }
+            }
+            data
+        }
+        None => {
+            let mut data = object.as_object().cloned().unwrap_or_default();
+            if let [key] = timestamp {
+                data.remove(key);
+            }
+            data
+        }
+    };
+    Ok(Reading {
+        measured_at,
+        data: Value::Object(data),
+    })
+}
+
+/// Sets `value` at a dotted path, creating the objects along the way.
+fn set(data: &mut Map<String, Value>, path: &[String], value: Value) -> Result<(), String> {
+    let (last, parents) = path.split_last().ok_or("Empty field path")?;
+    let mut object = data;
+    for key in parents {
+        object = object
+            .entry(key.to_owned())
+            .or_insert_with(|| Value::Object(Map::new()))
+            .as_object_mut()
+            .ok_or_else(|| format!("Field `{}` is both a value and an object", path.join(".")))?;
+    }
+    object.insert(last.to_owned(), value);
+    Ok(())
+}
+
+/// Inserts the readings in one transaction, created now like any other write
+/// while `measured_at` keeps when they were taken, so retention and archiving
+/// do not drop them on arrival. Capacity limits apply from the container's
+/// next write or sweep.
+pub async fn store<C: TransactionTrait>(
+    db: &C,
+    container_id: &str,
+    readings: Vec<Reading>,
+) -> Result<usize, DbErr> {
+    let imported = readings.len();
+    let now = Utc::now().naive_utc();
+    let txn = db.begin().await?;
+    let mut rows = readings
+        .into_iter()
+        .map(|reading| sensor_data::ActiveModel {
+            id: sea_orm::ActiveValue::Set(nanoid!(21)),
+            container_id: sea_orm::ActiveValue::Set(container_id.to_owned()),
+            data: sea_orm::ActiveValue::Set(Some(reading.data)),
+            created_at: sea_orm::ActiveValue::Set(now),
+            measured_at: sea_orm::ActiveValue::Set(reading.measured_at),
+        });
+    loop {
+        let chunk: Vec<sensor_data::ActiveModel> = rows.by_ref().take(CHUNK_SIZE).collect();
+        if chunk.is_empty() {
+            break;
+        }
+        SensorData::insert_many(chunk).exec(&txn).await?;
+    }
+    txn.commit().await?;
+    Ok(imported)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn strings(path: &[&str]) -> Vec<String> {
+        path.iter().map(|key| key.to_string()).collect()
+    }
+
+    fn millis(millis: i64) -> NaiveDateTime {
+        DateTime::from_timestamp_millis(millis).unwrap().naive_utc()
+    }
+
+    #[test]
+    fn parses_mappings() {
+        let mapping = Mapping::parse(" ts ", Some("temp:climate.temperature, humidity")).unwrap();
+        assert_eq!(mapping.timestamp, "ts");
+        assert_eq!(
+            mapping.fields.unwrap(),
+            [
+                ("temp".to_owned(), strings(&["climate", "temperature"])),
+                ("humidity".to_owned(), strings(&["humidity"])),
+            ]
+        );
+        assert!(Mapping::parse("ts", None).unwrap().fields.is_none());
+        assert!(Mapping::parse("ts", Some(":temperature")).is_err());
+        assert!(Mapping::parse("ts", Some("temp:climate..temperature")).is_err());
+    }
+
+    #[test]
+    fn reads_csv_records() {
+        let record = csv::StringRecord::from(vec!["1735787045678", "21.5", "", "on", "{\"a\":1}"]);
+        let fields = [
+            (1, strings(&["climate", "temperature"])),
+            (2, strings(&["humidity"])),
+            (3, strings(&["state"])),
+            (4, strings(&["nested"])),
+        ];
+        let reading = csv_reading(&record, 0, &fields).unwrap();
+        assert_eq!(reading.measured_at, millis(1735787045678));
+        // Empty cells are left out and cells that are not JSON become strings.
+        assert_eq!(
+            reading.data,
+            json!({ "climate": { "temperature": 21.5 }, "state": "on", "nested": { "a": 1 } })
+        );
+
+        let record = csv::StringRecord::from(vec!["yesterday", "1"]);
+        assert!(csv_reading(&record, 0, &fields[..1]).is_err());
+    }
+
+    #[test]
+    fn rejects_conflicting_csv_paths() {
+        let record = csv::StringRecord::from(vec!["0", "1", "2"]);
+        let fields = [(1, strings(&["a"])), (2, strings(&["a", "b"]))];
+        assert!(csv_reading(&record, 0, &fields).is_err());
+    }
+
+    #[test]
+    fn reads_json_objects() {
+        let mapping = Mapping::parse("ts", None).unwrap();
+        let object = json!({ "ts": "2025-01-02T03:04:05.678Z", "temperature": 21.5 });
+        let reading = json_reading(&object, &strings(&["ts"]), &mapping).unwrap();
+        assert_eq!(reading.measured_at, millis(1735787045678));
+        assert_eq!(reading.data, json!({ "temperature": 21.5 }));
+
+        let mapping = Mapping::parse("meta.ts", Some("climate.temp:temperature")).unwrap();
+        let object = json!({ "meta": { "ts": 1735787045678i64 }, "climate": { "temp": 3 } });
+        let reading = json_reading(&object, &strings(&["meta", "ts"]), &mapping).unwrap();
+        assert_eq!(reading.measured_at, millis(1735787045678));
+        assert_eq!(reading.data, json!({ "temperature": 3 }));
+    }
+
+    #[test]
+    fn rejects_invalid_json_rows() {
+        let mapping = Mapping::parse("ts", None).unwrap();
+        let timestamp = strings(&["ts"]);
+        assert!(json_reading(&json!([1]), &timestamp, &mapping).is_err());
+        assert!(json_reading(&json!({ "value": 1 }), &timestamp, &mapping).is_err());
+        assert!(json_reading(&json!({ "ts": true }), &timestamp, &mapping).is_err());
+    }
+}
@@ -0,0 +1,140 @@
+use std::{sync::Arc, time::Duration};
+
+use actix_web::rt;
+use futures_util::StreamExt;
+use redis::{Client, RedisResult};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::broadcast;
+
+use crate::{
+    entities::*,
+    notification::{
+        envelope::EventType,
+        scope::{Resource, Scope},
+    },
+};
+
+pub mod sse;
+pub mod ws;
+
+const CHANNEL: &str = "live_events";
+const CHANNEL_CAPACITY: usize = 1024;
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum LiveEvent {
+    /// A new reading.
+    SensorData {
+        scope: Scope,
+        entity: sensor_data::Model,
+    },
+    /// Any other change, e.g. a deleted reading or a created sensor.
+    Resource {
+        event_type: EventType,
+        scope: Scope,
+        resource: Value,
+    },
+}
+
+impl LiveEvent {
+    pub fn new(event_type: EventType, scope: Scope, resource: Resource) -> Self {
+        match (event_type, resource) {
+            (EventType::SensorDataCreated, Resource::SensorData(entity)) => LiveEvent::SensorData {
+                scope,
+                entity: entity.clone(),
+            },
+            (event_type, resource) => LiveEvent::Resource {
+                event_type,
+                scope,
+                resource: match resource {
+                    Resource::Sensor(entity) => serde_json::to_value(entity).unwrap(),
+                    Resource::DataContainer(entity) => serde_json::to_value(entity).unwrap(),
+                    Resource::SensorData(entity) => serde_json::to_value(entity).unwrap(),
+                    Resource::Alarm(entity) => serde_json::to_value(entity).unwrap(),
+                },
+            },
+        }
+    }
+}
+
+/// Fan-out of changes to connected live consumers (SSE and WebSocket).
+///
+/// Events go through a Redis channel so every replica behind the load
+/// balancer sees them; each replica relays the channel into its local
+/// broadcast.
+#[derive(Clone)]
+pub struct LiveHub {
+    redis: Client,
+    sender: broadcast::Sender<Arc<LiveEvent>>,
+}
+
+impl LiveHub {
+    pub fn new(redis: Client) -> Self {
+        let (sender, _) = broadcast::channel(CHANNEL_CAPACITY);
+        Self { redis, sender }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Arc<LiveEvent>> {
+        self.sender.subscribe()
+    }
+
+    pub async fn publish(&self, events: impl IntoIterator<Item = LiveEvent>) {
+        let events: Vec<LiveEvent> = events.into_iter().collect();
+        if events.is_empty() {
+            return;
+        }
+        if let Err(e) = self.publish_to_redis(&events).await {
+            // Keep local consumers up to date even when Redis is down.
+            eprintln!("Error publishing live events: {:?}", e);
+            for event in events {
+                self.broadcast(event);
+            }
+        }
+    }
+
+    /// Publishes the events in one pipeline over a single connection.
+    async fn publish_to_redis(&self, events: &[LiveEvent]) -> RedisResult<()> {
+        let mut redis_conn = self.redis.get_multiplexed_tokio_connection().await?;
+        let mut pipe = redis::pipe();
+        for event in events {
+            pipe.publish(CHANNEL, serde_json::to_string(event).unwrap())
+                .ignore();
+        }
+        pipe.query_async(&mut redis_conn).await
+    }
+
+    fn broadcast(&self, event: LiveEvent) {
+        // Sending only fails when nobody is listening, which is fine.
+        let _ = self.sender.send(Arc::new(event));
+    }
+
+    /// Starts relaying the Redis channel into the local broadcast,
+    /// reconnecting whenever the subscription drops.
+    pub fn spawn_relay(&self) {
+        let hub = self.clone();
+        rt::spawn(async move {
+            loop {
+                if let Err(e) = hub.relay().await {
+                    eprintln!("Error relaying live events: {:?}", e);
+                }
+                rt::time::sleep(RECONNECT_DELAY).await;
+            }
+        });
+    }
+
+    async fn relay(&self) -> RedisResult<()> {
+        let mut pubsub = self.redis.get_async_pubsub().await?;
+        pubsub.subscribe(CHANNEL).await?;
+        let mut messages = pubsub.on_message();
+
+        while let Some(message) = messages.next().await {
+            match serde_json::from_slice::<LiveEvent>(message.get_payload_bytes()) {
+                Ok(event) => self.broadcast(event),
+                Err(e) => eprintln!("Error decoding live event: {:?}", e),
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,138 @@
+use std::{cell::RefCell, rc::Rc, sync::Arc, time::Duration};
+
+use actix_web::{Error, rt::time::timeout, web::Bytes};
+use futures_util::{Stream, StreamExt, stream};
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder, QuerySelect};
+use tokio::sync::broadcast::Receiver;
+
+use super::LiveEvent;
+use crate::{
+    entities::{prelude::*, *},
+    pagination::{Cursor, Order, TimeField},
+};
+
+const KEEP_ALIVE: Duration = Duration::from_secs(15);
+const REPLAY_PAGE_SIZE: u64 = 500;
+
+/// Event stream of a container's readings. When `resume_from` is the reading
+/// named by the client's `Last-Event-ID`, everything stored after it is
+/// replayed before switching to live readings. Other changes within the
+/// container, such as deleted readings, are forwarded as they happen.
+///
+/// `receiver` must be subscribed before `resume_from` is looked up so nothing
+/// inserted in between is lost; live readings already replayed are skipped.
+pub fn sensor_data_stream(
+    db: DatabaseConnection,
+    receiver: Receiver<Arc<LiveEvent>>,
+    container_id: String,
+    resume_from: Option<sensor_data::Model>,
+) -> impl Stream<Item = Result<Bytes, Error>> {
+    let resume_from = resume_from
+        .as_ref()
+        .map(|entity| Cursor::of(TimeField::CreatedAt, Order::Asc, entity));
+    let replayed_until: Rc<RefCell<Option<Cursor>>> = Rc::new(RefCell::new(None));
+
+    let replay = stream::unfold(resume_from, {
+        let container_id = container_id.clone();
+        move |cursor| {
+            let db = db.clone();
+            let container_id = container_id.clone();
+            async move {
+                let cursor: Cursor = cursor?;
+                let page = SensorData::find()
+                    .filter(sensor_data::Column::ContainerId.eq(container_id))
+                    .filter(cursor.past())
+                    .order_by_asc(sensor_data::Column::CreatedAt)
+                    .order_by_asc(sensor_data::Column::Id)
+                    .limit(REPLAY_PAGE_SIZE)
+                    .all(&db)
+                    .await;
+                match page {
+                    Ok(page) => {
+                        let next = Cursor::of(TimeField::CreatedAt, Order::Asc, page.last()?);
+                        Some((stream::iter(page), Some(next)))
+                    }
+                    Err(e) => {
+                        eprintln!("Error replaying sensor data: {:?}", e);
+                        None
+                    }
+                }
+            }
+        }
+    })
+    .flatten()
+    .map(Frame::Replayed);
+
+    let live = stream::unfold(receiver, move |mut receiver| {
+        let container_id = container_id.clone();
+        async move {
+            loop {
+                match timeout(KEEP_ALIVE, receiver.recv()).await {
+                    Err(_) => return Some((Frame::KeepAlive, receiver)),
+                    Ok(Ok(event)) => {
+                        let in_container = match &*event {
+                            LiveEvent::SensorData { entity, .. } => {
+                                entity.container_id == container_id
+                            }
+                            LiveEvent::Resource { scope, .. } => {
+                                scope.container_id.as_ref() == Some(&container_id)
+                            }
+                        };
+                        if in_container {
+                            return Some((Frame::Live(event), receiver));
+                        }
+                    }
+                    // A lagging client is dropped so it reconnects with
+                    // Last-Event-ID and catches up from storage instead.
+                    Ok(Err(_)) => return None,
+                }
+            }
+        }
+    });
+
+    replay.chain(live).filter_map(move |frame| {
+        let bytes = match frame {
+            Frame::KeepAlive => Some(Bytes::from_static(b": keep-alive\n\n")),
+            Frame::Replayed(entity) => {
+                *replayed_until.borrow_mut() =
+                    Some(Cursor::of(TimeField::CreatedAt, Order::Asc, &entity));
+                Some(encode(&entity))
+            }
+            Frame::Live(event) => match &*event {
+                LiveEvent::SensorData { entity, .. } => {
+                    let cursor = Cursor::of(TimeField::CreatedAt, Order::Asc, entity);
+                    let replayed = replayed_until
+                        .borrow()
+                        .as_ref()
+                        .is_some_and(|replayed_until| cursor <= *replayed_until);
+                    (!replayed).then(|| encode(entity))
+                }
+                // Other changes have no place in the replay, so they carry no id.
+                LiveEvent::Resource {
+                    event_type,
+                    resource,
+                    ..
+                } => Some(Bytes::from(format!(
+                    "event: {}\ndata: {}\n\n",
+                    serde_json::to_value(event_type).unwrap().as_str().unwrap(),
+                    resource
+                ))),
+            },
+        };
+        async move { bytes.map(Ok) }
+    })
+}
+
+enum Frame {
+    KeepAlive,
+    Replayed(sensor_data::Model),
+    Live(Arc<LiveEvent>),
+}
+
+fn encode(entity: &sensor_data::Model) -> Bytes {
+    Bytes::from(format!(
+        "id: {}\nevent: sensor_data\ndata: {}\n\n",
+        entity.id,
+        serde_json::to_string(entity).unwrap()
+    ))
+}
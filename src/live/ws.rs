@@ -0,0 +1,218 @@
+use std::{
+    collections::HashSet,
+    time::{Duration, Instant},
+};
+
+use actix_web::{
+    Error, HttpRequest, HttpResponse, get, rt,
+    web::{Data, Payload, ServiceConfig},
+};
+use actix_ws::{Message, MessageStream, Session};
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::broadcast::error::RecvError;
+
+use super::LiveEvent;
+use crate::{
+    AppState,
+    entities::*,
+    notification::{envelope::EventType, scope::Scope},
+    routes::SensorData::{SensorDataCreate, ingest_sensor_data},
+};
+
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(15);
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(45);
+
+/// Resources a connection listens to. A reading is delivered when its
+/// container or any ancestor is selected.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+struct Selection {
+    homes: HashSet<String>,
+    applications: HashSet<String>,
+    sensors: HashSet<String>,
+    containers: HashSet<String>,
+}
+
+impl Selection {
+    fn add(&mut self, other: Selection) {
+        self.homes.extend(other.homes);
+        self.applications.extend(other.applications);
+        self.sensors.extend(other.sensors);
+        self.containers.extend(other.containers);
+    }
+
+    fn remove(&mut self, other: &Selection) {
+        self.homes.retain(|id| !other.homes.contains(id));
+        self.applications
+            .retain(|id| !other.applications.contains(id));
+        self.sensors.retain(|id| !other.sensors.contains(id));
+        self.containers.retain(|id| !other.containers.contains(id));
+    }
+
+    fn matches(&self, scope: &Scope) -> bool {
+        self.homes.contains(&scope.home_id)
+            || self.applications.contains(&scope.application_id)
+            || self.sensors.contains(&scope.sensor_id)
+            || scope
+                .container_id
+                .as_ref()
+                .is_some_and(|id| self.containers.contains(id))
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ClientMessage {
+    Subscribe(Selection),
+    Unsubscribe(Selection),
+    /// Same body and validation as `POST /sensor_data`.
+    Publish {
+        request_id: Option<String>,
+        #[serde(flatten)]
+        reading: SensorDataCreate,
+    },
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ServerMessage<'a> {
+    SensorData {
+        #[serde(flatten)]
+        scope: &'a Scope,
+        reading: &'a sensor_data::Model,
+    },
+    Event {
+        event_type: EventType,
+        #[serde(flatten)]
+        scope: &'a Scope,
+        resource: &'a Value,
+    },
+    Subscribed {
+        subscriptions: &'a Selection,
+    },
+    Published {
+        request_id: Option<String>,
+        reading: sensor_data::Model,
+    },
+    Error {
+        request_id: Option<String>,
+        message: String,
+    },
+    Lagged {
+        skipped: u64,
+    },
+}
+
+#[get("")]
+async fn connect(
+    state: Data<AppState>,
+    req: HttpRequest,
+    body: Payload,
+) -> Result<HttpResponse, Error> {
+    let (response, session, messages) = actix_ws::handle(&req, body)?;
+    rt::spawn(run(state, session, messages));
+    Ok(response)
+}
+
+async fn run(state: Data<AppState>, mut session: Session, mut messages: MessageStream) {
+    let mut receiver = state.live.subscribe();
+    let mut selection = Selection::default();
+    let mut heartbeat = rt::time::interval(HEARTBEAT_INTERVAL);
+    let mut last_pong = Instant::now();
+
+    let reason = loop {
+        tokio::select! {
+            message = messages.next() => match message {
+                Some(Ok(Message::Text(text))) => {
+                    let reply = handle_text(&state, &mut selection, &text).await;
+                    if send(&mut session, &reply).await.is_err() {
+                        break None;
+                    }
+                }
+                Some(Ok(Message::Ping(bytes))) => {
+                    if session.pong(&bytes).await.is_err() {
+                        break None;
+                    }
+                }
+                Some(Ok(Message::Pong(_))) => last_pong = Instant::now(),
+                Some(Ok(Message::Close(reason))) => break reason,
+                Some(Ok(_)) => {}
+                Some(Err(_)) | None => break None,
+            },
+            event = receiver.recv() => {
+                let sent = match event.as_deref() {
+                    Ok(LiveEvent::SensorData { scope, entity }) if selection.matches(scope) => {
+                        send(&mut session, &ServerMessage::SensorData { scope, reading: entity }).await
+                    }
+                    Ok(LiveEvent::Resource { event_type, scope, resource }) if selection.matches(scope) => {
+                        let message = ServerMessage::Event { event_type: *event_type, scope, resource };
+                        send(&mut session, &message).await
+                    }
+                    Ok(_) => Ok(()),
+                    Err(RecvError::Lagged(skipped)) => {
+                        send(&mut session, &ServerMessage::Lagged { skipped: *skipped }).await
+                    }
+                    Err(RecvError::Closed) => break None,
+                };
+                if sent.is_err() {
+                    break None;
+                }
+            }
+            _ = heartbeat.tick() => {
+                if last_pong.elapsed() > CLIENT_TIMEOUT || session.ping(b"").await.is_err() {
+                    break None;
+                }
+            }
+        }
+    };
+
+    let _ = session.close(reason).await;
+}
+
+async fn handle_text<'a>(
+    state: &AppState,
+    selection: &'a mut Selection,
+    text: &str,
+) -> ServerMessage<'a> {
+    match serde_json::from_str::<ClientMessage>(text) {
+        Ok(ClientMessage::Subscribe(other)) => {
+            selection.add(other);
+            ServerMessage::Subscribed {
+                subscriptions: selection,
+            }
+        }
+        Ok(ClientMessage::Unsubscribe(other)) => {
+            selection.remove(&other);
+            ServerMessage::Subscribed {
+                subscriptions: selection,
+            }
+        }
+        Ok(ClientMessage::Publish {
+            request_id,
+            reading,
+        }) => match ingest_sensor_data(state, reading).await {
+            Ok(reading) => ServerMessage::Published {
+                request_id,
+                reading,
+            },
+            Err(e) => ServerMessage::Error {
+                request_id,
+                message: e.to_string(),
+            },
+        },
+        Err(e) => ServerMessage::Error {
+            request_id: None,
+            message: e.to_string(),
+        },
+    }
+}
+
+async fn send(session: &mut Session, message: &ServerMessage<'_>) -> Result<(), actix_ws::Closed> {
+    session.text(serde_json::to_string(message).unwrap()).await
+}
+
+pub fn add_ws_route(cfg: &mut ServiceConfig) {
+    cfg.service(connect);
+}
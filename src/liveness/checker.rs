@@ -0,0 +1,51 @@
+use std::time::Duration;
+
+use actix_web::rt;
+use redis::{AsyncCommands, Client, RedisResult};
+use sea_orm::{DatabaseConnection, DbErr, TransactionTrait};
+
+use super::mark_offline;
+use crate::{
+    entities::sensor,
+    live::LiveHub,
+    routes::Sensor::PREFIX,
+    utils::{env_or, get_redis_id},
+};
+
+/// Starts the background task that marks silent sensors offline.
+pub fn spawn(db: DatabaseConnection, redis: Client, live: LiveHub) {
+    let interval = Duration::from_secs(env_or("SENSOR_LIVENESS_INTERVAL_SECONDS", 30));
+    rt::spawn(async move {
+        let mut interval = rt::time::interval(interval);
+
+        loop {
+            interval.tick().await;
+            if let Err(e) = check(&db, &redis, &live).await {
+                eprintln!("Error checking sensor liveness: {:?}", e);
+            }
+        }
+    });
+}
+
+async fn check(db: &DatabaseConnection, redis: &Client, live: &LiveHub) -> Result<(), DbErr> {
+    let txn = db.begin().await?;
+    let (silent, events) = mark_offline(&txn).await?;
+    txn.commit().await?;
+    live.publish(events).await;
+
+    if !silent.is_empty()
+        && let Err(e) = uncache(redis, &silent).await
+    {
+        eprintln!("Error uncaching offline sensors: {:?}", e);
+    }
+    Ok(())
+}
+
+async fn uncache(redis: &Client, sensors: &[sensor::Model]) -> RedisResult<()> {
+    let mut redis_conn = redis.get_multiplexed_tokio_connection().await?;
+    let keys: Vec<String> = sensors
+        .iter()
+        .map(|entity| get_redis_id(PREFIX, &entity.id))
+        .collect();
+    redis_conn.del(keys).await
+}
@@ -0,0 +1,88 @@
+//! Sensor liveness: `last_seen_at` is moved forward, every few seconds at
+//! most, when one of the sensor's containers stores a reading. A sensor with a `reporting_interval`
+//! that stays silent for longer is marked offline (`offline_since`) by the
+//! checker, and back online by its next reading, with a `sensor.offline` or
+//! `sensor.online` event each time.
+
+use chrono::{NaiveDateTime, TimeDelta, Utc};
+use sea_orm::{
+    ColumnTrait, Condition, ConnectionTrait, DbErr, EntityTrait, QueryFilter, sea_query::Expr,
+};
+
+use crate::{
+    entities::{prelude::*, *},
+    events::record,
+    live::LiveEvent,
+    notification::{envelope::EventType, scope::Resource},
+};
+
+pub mod checker;
+
+/// How stale `last_seen_at` may get before a reading moves it, so a busy
+/// sensor's row is not written, and locked, by every insert.
+const SEEN_GRANULARITY_SECONDS: i64 = 5;
+
+/// Marks the sensors as seen now and brings the offline ones back online.
+/// Run on the inserting transaction, after the insert.
+///
+/// `last_seen_at` is only moved once it is [`SEEN_GRANULARITY_SECONDS`] old,
+/// and only offline sensors are updated back online; that update locks their
+/// rows, so of two concurrent inserts only one finds a sensor offline.
+pub async fn touch<C: ConnectionTrait>(
+    db: &C,
+    sensor_ids: &[&str],
+) -> Result<Vec<LiveEvent>, DbErr> {
+    let now = Utc::now().naive_utc();
+    Sensor::update_many()
+        .col_expr(sensor::Column::LastSeenAt, Expr::value(now))
+        .filter(sensor::Column::Id.is_in(sensor_ids.iter().copied()))
+        .filter(
+            Condition::any()
+                .add(sensor::Column::LastSeenAt.is_null())
+                .add(
+                    sensor::Column::LastSeenAt
+                        .lt(now - TimeDelta::seconds(SEEN_GRANULARITY_SECONDS)),
+                ),
+        )
+        .exec(db)
+        .await?;
+    let recovered = Sensor::update_many()
+        .col_expr(
+            sensor::Column::OfflineSince,
+            Expr::value(Option::<NaiveDateTime>::None),
+        )
+        .filter(sensor::Column::Id.is_in(sensor_ids.iter().copied()))
+        .filter(sensor::Column::OfflineSince.is_not_null())
+        .exec_with_returning(db)
+        .await?;
+
+    let mut events = Vec::new();
+    for entity in &recovered {
+        events.extend(record(db, EventType::SensorOnline, Resource::Sensor(entity)).await?);
+    }
+    Ok(events)
+}
+
+/// Marks sensors silent for longer than their `reporting_interval` offline. A
+/// sensor that never reported counts from its creation.
+pub async fn mark_offline<C: ConnectionTrait>(
+    db: &C,
+) -> Result<(Vec<sensor::Model>, Vec<LiveEvent>), DbErr> {
+    let now = Utc::now().naive_utc();
+    let silent = Sensor::update_many()
+        .col_expr(sensor::Column::OfflineSince, Expr::value(Some(now)))
+        .filter(sensor::Column::ReportingInterval.is_not_null())
+        .filter(sensor::Column::OfflineSince.is_null())
+        .filter(Expr::cust_with_values(
+            "COALESCE(last_seen_at, created_at) + reporting_interval * INTERVAL '1 second' < $1",
+            [now],
+        ))
+        .exec_with_returning(db)
+        .await?;
+
+    let mut events = Vec::new();
+    for entity in &silent {
+        events.extend(record(db, EventType::SensorOffline, Resource::Sensor(entity)).await?);
+    }
+    Ok((silent, events))
+}
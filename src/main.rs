@@ -3,26 +3,62 @@ use actix_web::{
     App, HttpServer, get, main,
     web::{Data, scope},
 };
+use archive::ArchiveConfig;
 use dotenv::dotenv;
+use live::{LiveHub, ws::add_ws_route};
+use notification::NotificationConfig;
 use redis::Client;
 use routes::{
-    Application::add_application_route, DataContainer::add_data_container_routes,
-    Home::add_home_route, Sensor::add_sensor_route, SensorData::add_sensor_data_route,
-    Subscriber::add_subscriber_route,
+    Alarm::add_alarm_route, AlarmRule::add_alarm_rule_route, Application::add_application_route,
+    DataContainer::add_data_container_routes, Home::add_home_route, Sensor::add_sensor_route,
+    SensorData::add_sensor_data_route, Subscriber::add_subscriber_route,
 };
 use sea_orm::{Database, DatabaseConnection};
 use std::env;
 
+mod aggregation;
+
+mod alarm;
+
+mod archive;
+
+mod capacity;
+
 mod entities;
 
+mod events;
+
+mod export;
+
+mod filter;
+
+mod idempotency;
+
+mod import;
+
+mod live;
+
+mod liveness;
+
+mod metadata;
+
+mod notification;
+
+mod pagination;
+
 mod routes;
 
+mod schema;
+
 mod utils;
 
 #[derive(Clone)]
 struct AppState {
     db: DatabaseConnection,
     redis: Client,
+    notification: NotificationConfig,
+    live: LiveHub,
+    archive: ArchiveConfig,
 }
 
 #[get("/")]
@@ -41,14 +77,41 @@ async fn main() -> std::io::Result<()> {
             .await
             .expect("Failed to connect to database");
 
+    let args: Vec<String> = env::args().skip(1).collect();
+    if let Some("import") = args.first().map(String::as_str) {
+        return import::cli::run(&db, &args[1..]).await;
+    }
+
     let redis_url = env::var("REDIS_URL").expect("REDIS_URL must be set");
     let redis_client = Client::open(redis_url).expect("Failed to connect to Redis");
 
     let app_state = AppState {
         db,
-        redis: redis_client,
+        redis: redis_client.clone(),
+        notification: NotificationConfig::from_env(),
+        live: LiveHub::new(redis_client),
+        archive: ArchiveConfig::from_env(),
     };
 
+    notification::worker::spawn(app_state.db.clone(), app_state.notification.clone());
+    notification::retention::spawn(app_state.db.clone(), app_state.notification.clone());
+    app_state.live.spawn_relay();
+    capacity::sweeper::spawn(
+        app_state.db.clone(),
+        app_state.redis.clone(),
+        app_state.live.clone(),
+    );
+    archive::job::spawn(
+        app_state.db.clone(),
+        app_state.redis.clone(),
+        app_state.archive.clone(),
+    );
+    liveness::checker::spawn(
+        app_state.db.clone(),
+        app_state.redis.clone(),
+        app_state.live.clone(),
+    );
+
     HttpServer::new(move || {
         App::new()
             .wrap(Cors::default())
@@ -60,6 +123,9 @@ async fn main() -> std::io::Result<()> {
             .service(scope("/data_container").configure(add_data_container_routes))
             .service(scope("/sensor_data").configure(add_sensor_data_route))
             .service(scope("/subscribers").configure(add_subscriber_route))
+            .service(scope("/alarm_rule").configure(add_alarm_rule_route))
+            .service(scope("/alarm").configure(add_alarm_route))
+            .service(scope("/ws").configure(add_ws_route))
     })
     .bind(("0.0.0.0", 3000))?
     .run()
@@ -0,0 +1,250 @@
+//! Descriptive sensor metadata: what it measures (`kind`), the unit of each
+//! `data` field, where it is, what hardware it is and free-form `tags`.
+//! [`SensorQuery`] filters the sensor list endpoints on it.
+
+use sea_orm::{ColumnTrait, Condition, sea_query::Expr};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{entities::*, filter::parse_path};
+
+/// `units` maps `data` field paths to units, e.g.
+/// `{ "temperature": "°C", "gps.speed": "km/h" }`.
+pub fn check_units(units: &Value) -> Result<(), String> {
+    let Some(units) = units.as_object() else {
+        return Err("Units must be an object of field paths to units".to_owned());
+    };
+    for (path, unit) in units {
+        parse_path(path)?;
+        if unit.as_str().is_none_or(|unit| unit.trim().is_empty()) {
+            return Err(format!("Unit of `{}` must be a non-empty string", path));
+        }
+    }
+    Ok(())
+}
+
+/// A location is a WGS 84 latitude and longitude, both or neither.
+pub fn check_location(latitude: Option<f64>, longitude: Option<f64>) -> Result<(), String> {
+    match (latitude, longitude) {
+        (None, None) => Ok(()),
+        (Some(latitude), Some(longitude)) => {
+            if !(-90.0..=90.0).contains(&latitude) {
+                return Err("Latitude must be between -90 and 90".to_owned());
+            }
+            if !(-180.0..=180.0).contains(&longitude) {
+                return Err("Longitude must be between -180 and 180".to_owned());
+            }
+            Ok(())
+        }
+        _ => Err("Latitude and longitude must be set together".to_owned()),
+    }
+}
+
+/// Trims tags and drops duplicates, keeping the first occurrence's position.
+pub fn normalize_tags(tags: Vec<String>) -> Result<Vec<String>, String> {
+    let mut normalized: Vec<String> = Vec::with_capacity(tags.len());
+    for tag in tags {
+        let tag = tag.trim();
+        if tag.is_empty() {
+            return Err("Tags must not be empty".to_owned());
+        }
+        if !normalized.iter().any(|existing| existing == tag) {
+            normalized.push(tag.to_owned());
+        }
+    }
+    Ok(normalized)
+}
+
+/// `min_lon,min_lat,max_lon,max_lat`, GeoJSON order. A box whose `min_lon` is
+/// east of its `max_lon` crosses the antimeridian.
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(try_from = "String")]
+pub struct BoundingBox {
+    pub min_lon: f64,
+    pub min_lat: f64,
+    pub max_lon: f64,
+    pub max_lat: f64,
+}
+
+impl TryFrom<String> for BoundingBox {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let invalid = || format!("Invalid bounding box `{}`", value);
+        let corners = value
+            .split(',')
+            .map(|corner| corner.trim().parse::<f64>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| invalid())?;
+        let [min_lon, min_lat, max_lon, max_lat] = corners[..] else {
+            return Err(invalid());
+        };
+        check_location(Some(min_lat), Some(min_lon)).map_err(|_| invalid())?;
+        check_location(Some(max_lat), Some(max_lon)).map_err(|_| invalid())?;
+        if min_lat > max_lat {
+            return Err(invalid());
+        }
+        Ok(Self {
+            min_lon,
+            min_lat,
+            max_lon,
+            max_lat,
+        })
+    }
+}
+
+impl BoundingBox {
+    fn to_sql(self) -> Condition {
+        let longitude = if self.min_lon <= self.max_lon {
+            Condition::all().add(sensor::Column::Longitude.between(self.min_lon, self.max_lon))
+        } else {
+            Condition::any()
+                .add(sensor::Column::Longitude.gte(self.min_lon))
+                .add(sensor::Column::Longitude.lte(self.max_lon))
+        };
+        Condition::all()
+            .add(sensor::Column::Latitude.between(self.min_lat, self.max_lat))
+            .add(longitude)
+    }
+}
+
+/// Filters of the sensor list endpoints. `tags` is comma separated and
+/// matches sensors carrying all of them; `bbox` matches located sensors
+/// inside the box.
+#[derive(Deserialize)]
+pub struct SensorQuery {
+    pub kind: Option<String>,
+    pub manufacturer: Option<String>,
+    pub model_name: Option<String>,
+    pub tags: Option<String>,
+    pub bbox: Option<BoundingBox>,
+}
+
+impl SensorQuery {
+    pub fn to_sql(&self) -> Condition {
+        let mut condition = Condition::all();
+        if let Some(kind) = &self.kind {
+            condition = condition.add(sensor::Column::Kind.eq(kind));
+        }
+        if let Some(manufacturer) = &self.manufacturer {
+            condition = condition.add(sensor::Column::Manufacturer.eq(manufacturer));
+        }
+        if let Some(model_name) = &self.model_name {
+            condition = condition.add(sensor::Column::ModelName.eq(model_name));
+        }
+        if let Some(tags) = &self.tags {
+            let tags: Vec<String> = tags
+                .split(',')
+                .map(str::trim)
+                .filter(|tag| !tag.is_empty())
+                .map(str::to_owned)
+                .collect();
+            condition = condition.add(Expr::cust_with_values(
+                "tags @> $1::text[]",
+                [sea_orm::Value::from(tags)],
+            ));
+        }
+        if let Some(bbox) = self.bbox {
+            condition = condition.add(bbox.to_sql());
+        }
+        condition
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sea_orm::{DbBackend, EntityTrait, QueryFilter, QueryTrait};
+    use serde_json::json;
+
+    use super::*;
+    use crate::entities::prelude::Sensor;
+
+    fn bbox(value: &str) -> Result<BoundingBox, String> {
+        BoundingBox::try_from(value.to_owned())
+    }
+
+    fn sql(condition: Condition) -> String {
+        Sensor::find()
+            .filter(condition)
+            .build(DbBackend::Postgres)
+            .to_string()
+    }
+
+    #[test]
+    fn checks_units() {
+        assert!(check_units(&json!({ "temperature": "°C", "gps.speed": "km/h" })).is_ok());
+        assert!(check_units(&json!(["°C"])).is_err());
+        assert!(check_units(&json!({ "temperature": " " })).is_err());
+        assert!(check_units(&json!({ "temperature": 1 })).is_err());
+        assert!(check_units(&json!({ "gps..speed": "km/h" })).is_err());
+    }
+
+    #[test]
+    fn checks_locations() {
+        assert!(check_location(None, None).is_ok());
+        assert!(check_location(Some(-90.0), Some(180.0)).is_ok());
+        assert!(check_location(Some(90.5), Some(0.0)).is_err());
+        assert!(check_location(Some(0.0), Some(-180.5)).is_err());
+        assert!(check_location(Some(0.0), None).is_err());
+        assert!(check_location(None, Some(0.0)).is_err());
+    }
+
+    #[test]
+    fn normalizes_tags() {
+        let tags = vec![" roof ".to_owned(), "north".to_owned(), "roof".to_owned()];
+        assert_eq!(normalize_tags(tags).unwrap(), ["roof", "north"]);
+        assert_eq!(normalize_tags(Vec::new()).unwrap(), Vec::<String>::new());
+        assert!(normalize_tags(vec!["roof".to_owned(), "  ".to_owned()]).is_err());
+    }
+
+    #[test]
+    fn parses_bounding_boxes() {
+        let parsed = bbox("5.9, 45.8, 10.5, 47.8").unwrap();
+        assert_eq!(
+            (
+                parsed.min_lon,
+                parsed.min_lat,
+                parsed.max_lon,
+                parsed.max_lat
+            ),
+            (5.9, 45.8, 10.5, 47.8)
+        );
+        assert!(bbox("170,-20,-170,20").is_ok());
+
+        assert!(bbox("5.9,45.8,10.5").is_err());
+        assert!(bbox("5.9,45.8,10.5,47.8,1").is_err());
+        assert!(bbox("5.9,north,10.5,47.8").is_err());
+        assert!(bbox("5.9,45.8,190,47.8").is_err());
+        assert!(bbox("5.9,-95,10.5,47.8").is_err());
+        assert!(bbox("5.9,47.8,10.5,45.8").is_err());
+    }
+
+    #[test]
+    fn bounding_boxes_cross_the_antimeridian() {
+        let regular = sql(bbox("5.9,45.8,10.5,47.8").unwrap().to_sql());
+        assert!(regular.contains(r#""sensor"."latitude" BETWEEN 45.8 AND 47.8"#));
+        assert!(regular.contains(r#""sensor"."longitude" BETWEEN 5.9 AND 10.5"#));
+
+        let crossing = sql(bbox("170,-20,-170,20").unwrap().to_sql());
+        assert!(crossing.contains(r#""sensor"."latitude" BETWEEN -20 AND 20"#));
+        assert!(
+            crossing.contains(r#"("sensor"."longitude" >= 170 OR "sensor"."longitude" <= -170)"#)
+        );
+    }
+
+    #[test]
+    fn filters_sensors() {
+        let query = SensorQuery {
+            kind: Some("weather".to_owned()),
+            manufacturer: None,
+            model_name: Some("WS-2".to_owned()),
+            tags: Some("roof, ,north".to_owned()),
+            bbox: None,
+        };
+        let filtered = sql(query.to_sql());
+        assert!(filtered.contains(r#""sensor"."kind" = 'weather'"#));
+        assert!(filtered.contains(r#""sensor"."model_name" = 'WS-2'"#));
+        assert!(!filtered.contains("manufacturer\" ="));
+        assert!(filtered.contains("tags @> ARRAY ['roof','north']::text[]"));
+    }
+}
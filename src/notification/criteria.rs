@@ -0,0 +1,138 @@
+use chrono::{NaiveDateTime, TimeDelta};
+use sea_orm::{ColumnTrait, ConnectionTrait, DbErr, EntityTrait, QueryFilter, sea_query::Expr};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::envelope::EventType;
+use crate::{
+    entities::{prelude::*, *},
+    filter::Condition,
+};
+
+/// Per-subscription rules deciding which events are delivered.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Criteria {
+    pub event_types: Vec<EventType>,
+    /// Checked against the reading's `data`; every condition must hold.
+    pub conditions: Vec<Condition>,
+    pub min_interval_seconds: Option<u32>,
+}
+
+impl Default for Criteria {
+    fn default() -> Self {
+        Self {
+            event_types: EventType::ALL.to_vec(),
+            conditions: Vec::new(),
+            min_interval_seconds: None,
+        }
+    }
+}
+
+impl Criteria {
+    /// `None` when the stored criteria don't parse. Such a subscriber is
+    /// skipped rather than sent everything.
+    pub fn from_subscriber(subscriber: &subscribers::Model) -> Option<Self> {
+        match serde_json::from_value(subscriber.criteria.clone()) {
+            Ok(criteria) => Some(criteria),
+            Err(e) => {
+                eprintln!("Invalid criteria of subscriber {}: {:?}", subscriber.id, e);
+                None
+            }
+        }
+    }
+
+    /// `data` is the reading's payload for sensor data events and `None` for
+    /// events on other resources. Conditions only apply to readings, so a
+    /// container deletion is never filtered out by them. The minimum interval
+    /// is enforced when the notification is queued, see `claim_interval`.
+    pub fn accepts(&self, event_type: EventType, data: Option<&Value>) -> bool {
+        if !self.event_types.contains(&event_type) {
+            return false;
+        }
+        let Some(data) = data else {
+            return true;
+        };
+        self.conditions
+            .iter()
+            .all(|condition| condition.matches(data))
+    }
+}
+
+/// Moves the subscriber's `last_notified_at` to `now` unless it was notified
+/// less than `seconds` ago, and tells whether it did. The check and the
+/// update are one statement, so of two concurrent writes only one notifies.
+pub async fn claim_interval<C: ConnectionTrait>(
+    db: &C,
+    subscriber_id: &str,
+    seconds: u32,
+    now: NaiveDateTime,
+) -> Result<bool, DbErr> {
+    let cutoff = now - TimeDelta::seconds(seconds.into());
+    let claimed = Subscribers::update_many()
+        .col_expr(subscribers::Column::LastNotifiedAt, Expr::value(now))
+        .filter(subscribers::Column::Id.eq(subscriber_id))
+        .filter(
+            sea_orm::Condition::any()
+                .add(subscribers::Column::LastNotifiedAt.is_null())
+                .add(subscribers::Column::LastNotifiedAt.lte(cutoff)),
+        )
+        .exec(db)
+        .await?;
+    Ok(claimed.rows_affected > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+    use serde_json::json;
+
+    use super::*;
+    use crate::entities::sea_orm_active_enums::{NotificationContent, SubscriptionStatus};
+
+    fn subscriber(criteria: Value) -> subscribers::Model {
+        subscribers::Model {
+            id: "subscriber".to_owned(),
+            home_id: None,
+            application_id: None,
+            sensor_id: None,
+            container_id: Some("container".to_owned()),
+            notification_url: "http://localhost/hook".to_owned(),
+            notification_content: NotificationContent::Full,
+            secret: "secret".to_owned(),
+            status: SubscriptionStatus::Active,
+            criteria,
+            last_notified_at: None,
+            create_at: DateTime::UNIX_EPOCH.naive_utc(),
+        }
+    }
+
+    #[test]
+    fn defaults_to_every_event() {
+        let criteria = Criteria::from_subscriber(&subscriber(json!({}))).unwrap();
+        assert_eq!(criteria.event_types, EventType::ALL);
+        assert!(criteria.accepts(EventType::SensorDataCreated, Some(&json!({}))));
+    }
+
+    #[test]
+    fn skips_unreadable_criteria() {
+        let corrupt = subscriber(json!({ "conditions": ["temperature"] }));
+        assert!(Criteria::from_subscriber(&corrupt).is_none());
+    }
+
+    #[test]
+    fn filters_event_types_and_readings() {
+        let criteria = Criteria::from_subscriber(&subscriber(json!({
+            "event_types": ["sensor_data.created", "data_container.deleted"],
+            "conditions": ["temperature > 30"],
+        })))
+        .unwrap();
+        let hot = json!({ "temperature": 35 });
+        let cold = json!({ "temperature": 20 });
+        assert!(criteria.accepts(EventType::SensorDataCreated, Some(&hot)));
+        assert!(!criteria.accepts(EventType::SensorDataCreated, Some(&cold)));
+        assert!(!criteria.accepts(EventType::SensorDataDeleted, Some(&hot)));
+        // Conditions only apply to readings.
+        assert!(criteria.accepts(EventType::DataContainerDeleted, None));
+    }
+}
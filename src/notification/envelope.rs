@@ -0,0 +1,186 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use super::scope::{Resource, Scope};
+use crate::entities::{sea_orm_active_enums::NotificationContent, *};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EventType {
+    #[serde(rename = "sensor_data.created")]
+    SensorDataCreated,
+    #[serde(rename = "sensor_data.deleted")]
+    SensorDataDeleted,
+    #[serde(rename = "data_container.created")]
+    DataContainerCreated,
+    #[serde(rename = "data_container.deleted")]
+    DataContainerDeleted,
+    #[serde(rename = "sensor.created")]
+    SensorCreated,
+    #[serde(rename = "sensor.deleted")]
+    SensorDeleted,
+    #[serde(rename = "sensor.offline")]
+    SensorOffline,
+    #[serde(rename = "sensor.online")]
+    SensorOnline,
+    #[serde(rename = "alarm.raised")]
+    AlarmRaised,
+    #[serde(rename = "alarm.acknowledged")]
+    AlarmAcknowledged,
+    #[serde(rename = "alarm.cleared")]
+    AlarmCleared,
+}
+
+impl EventType {
+    pub const ALL: [EventType; 11] = [
+        EventType::SensorDataCreated,
+        EventType::SensorDataDeleted,
+        EventType::DataContainerCreated,
+        EventType::DataContainerDeleted,
+        EventType::SensorCreated,
+        EventType::SensorDeleted,
+        EventType::SensorOffline,
+        EventType::SensorOnline,
+        EventType::AlarmRaised,
+        EventType::AlarmAcknowledged,
+        EventType::AlarmCleared,
+    ];
+
+    /// Deleting a sensor or container also deletes the subscriptions on it,
+    /// so these events are delivered without the subscriber.
+    pub fn deletes_subscriptions(self) -> bool {
+        matches!(
+            self,
+            EventType::SensorDeleted | EventType::DataContainerDeleted
+        )
+    }
+}
+
+/// Body posted to a subscriber's `notification_url`.
+#[derive(Serialize)]
+pub struct Notification {
+    pub event_type: EventType,
+    pub subscription_id: String,
+    #[serde(flatten)]
+    pub scope: Scope,
+    pub resource: Value,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl Notification {
+    pub fn new(
+        event_type: EventType,
+        subscriber: &subscribers::Model,
+        scope: &Scope,
+        resource: Resource,
+    ) -> Self {
+        let content = subscriber.notification_content;
+        Self {
+            event_type,
+            subscription_id: subscriber.id.to_owned(),
+            scope: scope.clone(),
+            resource: match resource {
+                Resource::Sensor(entity) => sensor_resource(content, entity),
+                Resource::DataContainer(entity) => data_container_resource(content, entity),
+                Resource::SensorData(entity) => sensor_data_resource(content, entity),
+                Resource::Alarm(entity) => alarm_resource(content, entity),
+            },
+            timestamp: Utc::now(),
+        }
+    }
+}
+
+/// Body posted once per subscriber for readings ingested in one batch, in
+/// place of a `sensor_data.created` notification per reading.
+#[derive(Serialize)]
+pub struct BatchNotification {
+    pub event_type: &'static str,
+    pub subscription_id: String,
+    pub resources: Vec<BatchItem>,
+    pub timestamp: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+pub struct BatchItem {
+    #[serde(flatten)]
+    pub scope: Scope,
+    pub resource: Value,
+}
+
+impl BatchNotification {
+    pub fn new(
+        subscriber: &subscribers::Model,
+        readings: &[(&Scope, &sensor_data::Model)],
+    ) -> Self {
+        Self {
+            event_type: "sensor_data.batch_created",
+            subscription_id: subscriber.id.to_owned(),
+            resources: readings
+                .iter()
+                .map(|(scope, entity)| BatchItem {
+                    scope: (*scope).clone(),
+                    resource: sensor_data_resource(subscriber.notification_content, entity),
+                })
+                .collect(),
+            timestamp: Utc::now(),
+        }
+    }
+}
+
+/// Challenge sent to a notification URL before the subscription is activated.
+#[derive(Serialize)]
+pub struct Verification<'a> {
+    pub event_type: &'static str,
+    pub subscription_id: &'a str,
+    pub challenge: &'a str,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl<'a> Verification<'a> {
+    pub fn new(subscriber: &'a subscribers::Model, challenge: &'a str) -> Self {
+        Self {
+            event_type: "subscription.verification",
+            subscription_id: &subscriber.id,
+            challenge,
+            timestamp: Utc::now(),
+        }
+    }
+}
+
+/// Shapes a reading according to the subscriber's content mode. A reading is
+/// only ever written as a whole, so `Modified` carries its `data` and id.
+fn sensor_data_resource(content: NotificationContent, entity: &sensor_data::Model) -> Value {
+    match content {
+        NotificationContent::Full => serde_json::to_value(entity).unwrap(),
+        NotificationContent::Id => json!({ "id": entity.id }),
+        NotificationContent::Modified => json!({ "id": entity.id, "data": entity.data }),
+    }
+}
+
+fn sensor_resource(content: NotificationContent, entity: &sensor::Model) -> Value {
+    match content {
+        NotificationContent::Full => serde_json::to_value(entity).unwrap(),
+        NotificationContent::Id | NotificationContent::Modified => json!({ "id": entity.id }),
+    }
+}
+
+/// `Modified` carries what a transition changes: the state and its timestamps.
+fn alarm_resource(content: NotificationContent, entity: &alarm::Model) -> Value {
+    match content {
+        NotificationContent::Full => serde_json::to_value(entity).unwrap(),
+        NotificationContent::Id => json!({ "id": entity.id }),
+        NotificationContent::Modified => json!({
+            "id": entity.id,
+            "state": entity.state,
+            "acknowledged_at": entity.acknowledged_at,
+            "cleared_at": entity.cleared_at,
+        }),
+    }
+}
+
+fn data_container_resource(content: NotificationContent, entity: &data_container::Model) -> Value {
+    match content {
+        NotificationContent::Full => serde_json::to_value(entity).unwrap(),
+        NotificationContent::Id | NotificationContent::Modified => json!({ "id": entity.id }),
+    }
+}
@@ -0,0 +1,231 @@
+use std::{collections::HashMap, time::Duration};
+
+use chrono::{NaiveDateTime, Utc};
+use nanoid::nanoid;
+use sea_orm::{ColumnTrait, Condition, ConnectionTrait, DbErr, EntityTrait, QueryFilter};
+use serde::Serialize;
+use surf::Client;
+
+use crate::{
+    entities::{prelude::*, sea_orm_active_enums::SubscriptionStatus, *},
+    utils::env_or,
+};
+use criteria::{Criteria, claim_interval};
+use envelope::{BatchNotification, EventType, Notification};
+use scope::{Resource, Scope};
+
+pub mod criteria;
+pub mod envelope;
+pub mod retention;
+pub mod scope;
+pub mod signature;
+pub mod verification;
+pub mod worker;
+
+#[derive(Clone)]
+pub struct NotificationConfig {
+    pub max_attempts: i32,
+    pub retry_base: Duration,
+    pub retry_max: Duration,
+    pub poll_interval: Duration,
+    pub batch_size: u64,
+    pub timeout: Duration,
+    /// Days delivery attempts are kept for.
+    pub delivery_retention_days: i64,
+    pub prune_interval: Duration,
+}
+
+impl NotificationConfig {
+    pub fn from_env() -> Self {
+        Self {
+            max_attempts: env_or("NOTIFICATION_MAX_ATTEMPTS", 8),
+            retry_base: Duration::from_secs(env_or("NOTIFICATION_RETRY_BASE_SECONDS", 5)),
+            retry_max: Duration::from_secs(env_or("NOTIFICATION_RETRY_MAX_SECONDS", 3600)),
+            poll_interval: Duration::from_secs(env_or("NOTIFICATION_POLL_INTERVAL_SECONDS", 2)),
+            batch_size: env_or("NOTIFICATION_BATCH_SIZE", 50),
+            timeout: Duration::from_secs(env_or("NOTIFICATION_TIMEOUT_SECONDS", 10)),
+            delivery_retention_days: env_or("NOTIFICATION_DELIVERY_RETENTION_DAYS", 30),
+            prune_interval: Duration::from_secs(env_or(
+                "NOTIFICATION_PRUNE_INTERVAL_SECONDS",
+                3600,
+            )),
+        }
+    }
+
+    /// Delay before retrying a delivery that has failed `attempts` times.
+    pub fn backoff(&self, attempts: i32) -> Duration {
+        let exponent = (attempts - 1).clamp(0, 31) as u32;
+        self.retry_base
+            .saturating_mul(2u32.saturating_pow(exponent))
+            .min(self.retry_max)
+    }
+}
+
+pub fn http_client(config: &NotificationConfig) -> Client {
+    surf::Config::new()
+        .set_timeout(Some(config.timeout))
+        .try_into()
+        .expect("Failed to build notification client")
+}
+
+/// Queues `event_type` for every active subscriber of the resource or one of
+/// its ancestors whose criteria accept it.
+///
+/// Run this on the same transaction as the write so a change is never stored
+/// without its notifications. For deletions call it before deleting: the
+/// resource's subscribers are removed with it, but rows of events that
+/// [delete subscriptions](EventType::deletes_subscriptions) keep the URL and
+/// secret they need to be delivered.
+pub async fn enqueue<C: ConnectionTrait>(
+    db: &C,
+    event_type: EventType,
+    scope: &Scope,
+    resource: Resource<'_>,
+) -> Result<(), DbErr> {
+    let now = Utc::now().naive_utc();
+    let data = resource.data();
+
+    let candidates: Vec<(subscribers::Model, Criteria)> = Subscribers::find()
+        .filter(scope.subscriber_condition())
+        .filter(subscribers::Column::Status.eq(SubscriptionStatus::Active))
+        .all(db)
+        .await?
+        .into_iter()
+        .filter_map(|subscriber| {
+            let criteria = Criteria::from_subscriber(&subscriber)?;
+            criteria
+                .accepts(event_type, data)
+                .then_some((subscriber, criteria))
+        })
+        .collect();
+
+    let subscriber_list = if data.is_some() {
+        throttle(db, candidates, now).await?
+    } else {
+        candidates
+            .into_iter()
+            .map(|(subscriber, _)| subscriber)
+            .collect()
+    };
+    if subscriber_list.is_empty() {
+        return Ok(());
+    }
+
+    NotificationQueue::insert_many(subscriber_list.into_iter().map(|subscriber| {
+        let notification = Notification::new(event_type, &subscriber, scope, resource);
+        queue_row(
+            subscriber,
+            resource.sensor_data_id(),
+            notification,
+            event_type.deletes_subscriptions(),
+            now,
+        )
+    }))
+    .exec(db)
+    .await?;
+    Ok(())
+}
+
+/// Queues one `sensor_data.batch_created` notification per subscriber,
+/// holding every reading of the batch its criteria accept.
+pub async fn enqueue_batch<C: ConnectionTrait>(
+    db: &C,
+    readings: &[(&Scope, &sensor_data::Model)],
+) -> Result<(), DbErr> {
+    let now = Utc::now().naive_utc();
+
+    let condition = readings
+        .iter()
+        .fold(Condition::any(), |condition, (scope, _)| {
+            condition.add(scope.subscriber_condition())
+        });
+    let subscriber_list = Subscribers::find()
+        .filter(condition)
+        .filter(subscribers::Column::Status.eq(SubscriptionStatus::Active))
+        .all(db)
+        .await?;
+
+    let mut candidates = Vec::new();
+    let mut accepted_by_subscriber = HashMap::new();
+    for subscriber in subscriber_list {
+        let Some(criteria) = Criteria::from_subscriber(&subscriber) else {
+            continue;
+        };
+        let accepted: Vec<(&Scope, &sensor_data::Model)> = readings
+            .iter()
+            .filter(|(scope, entity)| {
+                scope.covers(&subscriber)
+                    && criteria.accepts(
+                        EventType::SensorDataCreated,
+                        Resource::SensorData(entity).data(),
+                    )
+            })
+            .copied()
+            .collect();
+        if accepted.is_empty() {
+            continue;
+        }
+        accepted_by_subscriber.insert(subscriber.id.to_owned(), accepted);
+        candidates.push((subscriber, criteria));
+    }
+
+    let rows: Vec<_> = throttle(db, candidates, now)
+        .await?
+        .into_iter()
+        .map(|subscriber| {
+            let accepted = &accepted_by_subscriber[&subscriber.id];
+            let notification = BatchNotification::new(&subscriber, accepted);
+            queue_row(subscriber, None, notification, false, now)
+        })
+        .collect();
+    if rows.is_empty() {
+        return Ok(());
+    }
+
+    NotificationQueue::insert_many(rows).exec(db).await?;
+    Ok(())
+}
+
+/// The subscribers whose `min_interval_seconds` lets them be notified of a
+/// reading now, with `last_notified_at` moved forward for the throttled ones.
+/// Subscribers without an interval are left alone, so a busy container does
+/// not make every insert write all of its subscribers.
+async fn throttle<C: ConnectionTrait>(
+    db: &C,
+    candidates: Vec<(subscribers::Model, Criteria)>,
+    now: NaiveDateTime,
+) -> Result<Vec<subscribers::Model>, DbErr> {
+    let mut allowed = Vec::new();
+    for (subscriber, criteria) in candidates {
+        if let Some(seconds) = criteria.min_interval_seconds
+            && !claim_interval(db, &subscriber.id, seconds, now).await?
+        {
+            continue;
+        }
+        allowed.push(subscriber);
+    }
+    Ok(allowed)
+}
+
+/// Snapshots the subscriber's URL and secret so a row flagged
+/// `resource_deleted` can still be delivered after the subscription is gone.
+fn queue_row(
+    subscriber: subscribers::Model,
+    sensor_data_id: Option<String>,
+    notification: impl Serialize,
+    resource_deleted: bool,
+    now: NaiveDateTime,
+) -> notification_queue::ActiveModel {
+    notification_queue::ActiveModel {
+        id: sea_orm::ActiveValue::Set(nanoid!(21)),
+        subscriber_id: sea_orm::ActiveValue::Set(Some(subscriber.id)),
+        sensor_data_id: sea_orm::ActiveValue::Set(sensor_data_id),
+        notification_url: sea_orm::ActiveValue::Set(subscriber.notification_url),
+        secret: sea_orm::ActiveValue::Set(subscriber.secret),
+        payload: sea_orm::ActiveValue::Set(serde_json::to_value(notification).unwrap()),
+        attempts: sea_orm::ActiveValue::Set(0),
+        next_attempt_at: sea_orm::ActiveValue::Set(now),
+        resource_deleted: sea_orm::ActiveValue::Set(resource_deleted),
+        ..Default::default()
+    }
+}
@@ -0,0 +1,31 @@
+use actix_web::rt;
+use chrono::{TimeDelta, Utc};
+use sea_orm::{ColumnTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter};
+
+use super::NotificationConfig;
+use crate::entities::{prelude::*, *};
+
+/// Starts the background task that deletes delivery attempts older than
+/// `NOTIFICATION_DELIVERY_RETENTION_DAYS`, which would otherwise grow with
+/// every notification sent.
+pub fn spawn(db: DatabaseConnection, config: NotificationConfig) {
+    rt::spawn(async move {
+        let mut interval = rt::time::interval(config.prune_interval);
+
+        loop {
+            interval.tick().await;
+            if let Err(e) = prune(&db, &config).await {
+                eprintln!("Error pruning notification deliveries: {:?}", e);
+            }
+        }
+    });
+}
+
+async fn prune(db: &DatabaseConnection, config: &NotificationConfig) -> Result<(), DbErr> {
+    let cutoff = Utc::now().naive_utc() - TimeDelta::days(config.delivery_retention_days);
+    NotificationDelivery::delete_many()
+        .filter(notification_delivery::Column::CreatedAt.lt(cutoff))
+        .exec(db)
+        .await?;
+    Ok(())
+}
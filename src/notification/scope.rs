@@ -0,0 +1,127 @@
+use sea_orm::{ColumnTrait, ConnectionTrait, DbErr, EntityTrait, ModelTrait, sea_query::Condition};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::entities::{prelude::*, *};
+
+/// Where an event happened in the Home → Application → Sensor → DataContainer
+/// tree. A subscription on any of these ids receives the event.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Scope {
+    pub home_id: String,
+    pub application_id: String,
+    pub sensor_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub container_id: Option<String>,
+}
+
+impl Scope {
+    pub async fn of_sensor<C: ConnectionTrait>(
+        db: &C,
+        sensor: &sensor::Model,
+    ) -> Result<Option<Self>, DbErr> {
+        Ok(sensor
+            .find_related(Application)
+            .one(db)
+            .await?
+            .map(|application| Self {
+                home_id: application.home_id,
+                application_id: application.id,
+                sensor_id: sensor.id.to_owned(),
+                container_id: None,
+            }))
+    }
+
+    pub async fn of_sensor_data<C: ConnectionTrait>(
+        db: &C,
+        entity: &sensor_data::Model,
+    ) -> Result<Option<Self>, DbErr> {
+        match DataContainer::find_by_id(&entity.container_id)
+            .one(db)
+            .await?
+        {
+            Some(container) => Self::of_data_container(db, &container).await,
+            None => Ok(None),
+        }
+    }
+
+    pub async fn of_data_container<C: ConnectionTrait>(
+        db: &C,
+        container: &data_container::Model,
+    ) -> Result<Option<Self>, DbErr> {
+        Ok(Sensor::find_by_id(&container.sensor_id)
+            .find_also_related(Application)
+            .one(db)
+            .await?
+            .and_then(|(sensor, application)| {
+                application.map(|application| Self {
+                    home_id: application.home_id,
+                    application_id: application.id,
+                    sensor_id: sensor.id,
+                    container_id: Some(container.id.to_owned()),
+                })
+            }))
+    }
+
+    pub async fn of_alarm<C: ConnectionTrait>(
+        db: &C,
+        alarm: &alarm::Model,
+    ) -> Result<Option<Self>, DbErr> {
+        match DataContainer::find_by_id(&alarm.container_id)
+            .one(db)
+            .await?
+        {
+            Some(container) => Self::of_data_container(db, &container).await,
+            None => Ok(None),
+        }
+    }
+
+    /// Matches subscribers attached to this resource or any of its ancestors.
+    pub fn subscriber_condition(&self) -> Condition {
+        let mut condition = Condition::any()
+            .add(subscribers::Column::HomeId.eq(&self.home_id))
+            .add(subscribers::Column::ApplicationId.eq(&self.application_id))
+            .add(subscribers::Column::SensorId.eq(&self.sensor_id));
+        if let Some(container_id) = &self.container_id {
+            condition = condition.add(subscribers::Column::ContainerId.eq(container_id));
+        }
+        condition
+    }
+
+    /// In-memory counterpart of [`Scope::subscriber_condition`].
+    pub fn covers(&self, subscriber: &subscribers::Model) -> bool {
+        subscriber.home_id.as_ref() == Some(&self.home_id)
+            || subscriber.application_id.as_ref() == Some(&self.application_id)
+            || subscriber.sensor_id.as_ref() == Some(&self.sensor_id)
+            || subscriber
+                .container_id
+                .as_ref()
+                .is_some_and(|id| self.container_id.as_ref() == Some(id))
+    }
+}
+
+/// The resource an event is about.
+#[derive(Clone, Copy)]
+pub enum Resource<'a> {
+    Sensor(&'a sensor::Model),
+    DataContainer(&'a data_container::Model),
+    SensorData(&'a sensor_data::Model),
+    Alarm(&'a alarm::Model),
+}
+
+impl Resource<'_> {
+    /// Payload that notification criteria conditions are checked against.
+    pub fn data(&self) -> Option<&Value> {
+        match self {
+            Resource::SensorData(entity) => Some(entity.data.as_ref().unwrap_or(&Value::Null)),
+            _ => None,
+        }
+    }
+
+    pub fn sensor_data_id(&self) -> Option<String> {
+        match self {
+            Resource::SensorData(entity) => Some(entity.id.to_owned()),
+            _ => None,
+        }
+    }
+}
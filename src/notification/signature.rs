@@ -0,0 +1,71 @@
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use nanoid::nanoid;
+use serde::Serialize;
+use sha2::Sha256;
+use surf::{Client, RequestBuilder, http::mime};
+
+pub const SIGNATURE_HEADER: &str = "X-M2M-Signature";
+pub const TIMESTAMP_HEADER: &str = "X-M2M-Timestamp";
+
+pub fn generate_secret() -> String {
+    nanoid!(32)
+}
+
+/// Signs a notification body for the `X-M2M-Signature` header.
+///
+/// The MAC covers `"{timestamp}.{body}"` so receivers can reject replays by
+/// checking `X-M2M-Timestamp` before comparing signatures.
+pub fn sign(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Builds a POST of `payload` to `url`, signed with `secret`.
+pub fn signed_post(
+    client: &Client,
+    url: &str,
+    secret: &str,
+    payload: &impl Serialize,
+) -> Result<RequestBuilder, String> {
+    let body = serde_json::to_vec(payload).map_err(|e| e.to_string())?;
+    let timestamp = Utc::now().timestamp();
+    Ok(client
+        .post(url)
+        .header(TIMESTAMP_HEADER, timestamp.to_string())
+        .header(SIGNATURE_HEADER, sign(secret, timestamp, &body))
+        .content_type(mime::JSON)
+        .body(body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signs_timestamp_and_body() {
+        assert_eq!(
+            sign("secret", 1700000000, br#"{"a":1}"#),
+            "sha256=49f24e537407743fa4a0242bb63b94b9a47ee99cbbe071ccd8a22550ae411686"
+        );
+    }
+
+    #[test]
+    fn signature_depends_on_every_input() {
+        let signature = sign("secret", 1700000000, b"body");
+        assert_ne!(sign("other", 1700000000, b"body"), signature);
+        assert_ne!(sign("secret", 1700000001, b"body"), signature);
+        assert_ne!(sign("secret", 1700000000, b"body!"), signature);
+    }
+
+    #[test]
+    fn generates_distinct_secrets() {
+        let secret = generate_secret();
+        assert_eq!(secret.len(), 32);
+        assert_ne!(generate_secret(), secret);
+    }
+}
@@ -0,0 +1,44 @@
+use nanoid::nanoid;
+use serde_json::Value;
+use surf::Client;
+
+use super::{envelope::Verification, signature::signed_post};
+use crate::entities::{sea_orm_active_enums::SubscriptionStatus, *};
+
+/// Sends a challenge to the subscriber's notification URL, signed like any
+/// notification with the subscriber's secret.
+///
+/// The endpoint must answer with a 2xx whose body is either the bare challenge
+/// or a JSON object with a matching `challenge` field.
+pub async fn verify(client: &Client, subscriber: &subscribers::Model) -> SubscriptionStatus {
+    let challenge = nanoid!(32);
+    let Ok(request) = signed_post(
+        client,
+        &subscriber.notification_url,
+        &subscriber.secret,
+        &Verification::new(subscriber, &challenge),
+    ) else {
+        return SubscriptionStatus::Failed;
+    };
+
+    match request.await {
+        Ok(mut response) if response.status().is_success() => match response.body_string().await {
+            Ok(body) if echoes_challenge(&body, &challenge) => SubscriptionStatus::Active,
+            _ => SubscriptionStatus::Failed,
+        },
+        _ => SubscriptionStatus::Failed,
+    }
+}
+
+fn echoes_challenge(body: &str, challenge: &str) -> bool {
+    body.trim() == challenge
+        || serde_json::from_str::<Value>(body)
+            .ok()
+            .and_then(|value| {
+                value
+                    .get("challenge")
+                    .and_then(Value::as_str)
+                    .map(str::to_owned)
+            })
+            .is_some_and(|echoed| echoed == challenge)
+}
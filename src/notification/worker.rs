@@ -0,0 +1,258 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use actix_web::rt;
+use chrono::{TimeDelta, Utc};
+use futures_util::future::join_all;
+use nanoid::nanoid;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, DatabaseTransaction, DbErr, EntityTrait,
+    QueryFilter, QueryOrder, QuerySelect, TransactionTrait,
+    sea_query::{Expr, LockBehavior, LockType},
+};
+use serde_json::Value;
+use surf::Client;
+
+use super::{NotificationConfig, http_client, signature::signed_post};
+use crate::entities::{prelude::*, sea_orm_active_enums::SubscriptionStatus, *};
+
+/// Extra time a claimed row stays leased beyond the delivery timeout, for
+/// recording the outcome.
+const LEASE_SLACK: TimeDelta = TimeDelta::seconds(30);
+
+/// Starts the background task that drains `notification_queue`.
+///
+/// Due rows are claimed with `FOR UPDATE SKIP LOCKED` and leased by moving
+/// their `next_attempt_at` forward, so several replicas can run the worker
+/// against the same database without delivering twice. Deliveries happen
+/// outside any transaction and each outcome is recorded on its own; a worker
+/// that dies mid-delivery leaves its rows to be retried once the lease ends.
+pub fn spawn(db: DatabaseConnection, config: NotificationConfig) {
+    rt::spawn(async move {
+        let client = http_client(&config);
+        let mut interval = rt::time::interval(config.poll_interval);
+
+        loop {
+            interval.tick().await;
+            if let Err(e) = process_due(&db, &client, &config).await {
+                eprintln!("Error processing notification queue: {:?}", e);
+            }
+        }
+    });
+}
+
+async fn process_due(
+    db: &DatabaseConnection,
+    client: &Client,
+    config: &NotificationConfig,
+) -> Result<(), DbErr> {
+    let deliveries = claim(db, config).await?;
+    if deliveries.is_empty() {
+        return Ok(());
+    }
+
+    let attempts = join_all(deliveries.iter().map(|delivery| {
+        deliver(
+            client,
+            &delivery.url,
+            &delivery.secret,
+            &delivery.job.payload,
+        )
+    }))
+    .await;
+
+    for (delivery, attempt) in deliveries.into_iter().zip(attempts) {
+        let id = delivery.job.id.to_owned();
+        if let Err(e) = record_outcome(db, config, delivery.job, attempt).await {
+            eprintln!("Error recording notification delivery {}: {:?}", id, e);
+        }
+    }
+    Ok(())
+}
+
+/// A claimed row and where to post it.
+struct Delivery {
+    job: notification_queue::Model,
+    url: String,
+    secret: String,
+}
+
+/// Locks a batch of due rows and leases them for the length of a delivery.
+///
+/// Rows go to their subscriber's current URL and secret, so URL changes and
+/// secret rotations apply to retries, and only while it is active: rows of
+/// a subscriber waiting for verification or gone are dead-lettered. Only
+/// rows of events that deleted the subscription with their resource fall
+/// back to the URL and secret captured when they were queued.
+async fn claim(
+    db: &DatabaseConnection,
+    config: &NotificationConfig,
+) -> Result<Vec<Delivery>, DbErr> {
+    let now = Utc::now().naive_utc();
+    let lease = TimeDelta::from_std(config.timeout).unwrap_or(TimeDelta::days(1)) + LEASE_SLACK;
+
+    let txn = db.begin().await?;
+    let jobs = NotificationQueue::find()
+        .filter(notification_queue::Column::NextAttemptAt.lte(now))
+        .order_by_asc(notification_queue::Column::NextAttemptAt)
+        .limit(config.batch_size)
+        .lock_with_behavior(LockType::Update, LockBehavior::SkipLocked)
+        .all(&txn)
+        .await?;
+    let subscriber_map: HashMap<String, subscribers::Model> = Subscribers::find()
+        .filter(
+            subscribers::Column::Id.is_in(jobs.iter().filter_map(|job| job.subscriber_id.clone())),
+        )
+        .all(&txn)
+        .await?
+        .into_iter()
+        .map(|subscriber| (subscriber.id.clone(), subscriber))
+        .collect();
+
+    let mut deliveries = Vec::new();
+    for job in jobs {
+        let subscriber = job
+            .subscriber_id
+            .as_ref()
+            .and_then(|id| subscriber_map.get(id));
+        let (url, secret) = match subscriber {
+            Some(subscriber) if subscriber.status == SubscriptionStatus::Active => (
+                subscriber.notification_url.to_owned(),
+                subscriber.secret.to_owned(),
+            ),
+            Some(_) => {
+                let (attempts, error) = (job.attempts, "Subscriber is not active".to_owned());
+                dead_letter(&txn, job, attempts, error).await?;
+                continue;
+            }
+            None if job.resource_deleted => {
+                (job.notification_url.to_owned(), job.secret.to_owned())
+            }
+            None => {
+                let (attempts, error) = (job.attempts, "Subscriber was deleted".to_owned());
+                dead_letter(&txn, job, attempts, error).await?;
+                continue;
+            }
+        };
+        deliveries.push(Delivery { job, url, secret });
+    }
+
+    if !deliveries.is_empty() {
+        NotificationQueue::update_many()
+            .col_expr(
+                notification_queue::Column::NextAttemptAt,
+                Expr::value(now + lease),
+            )
+            .filter(
+                notification_queue::Column::Id
+                    .is_in(deliveries.iter().map(|delivery| &delivery.job.id)),
+            )
+            .exec(&txn)
+            .await?;
+    }
+    txn.commit().await?;
+    Ok(deliveries)
+}
+
+/// Logs the attempt and removes, reschedules or dead-letters the row.
+async fn record_outcome(
+    db: &DatabaseConnection,
+    config: &NotificationConfig,
+    job: notification_queue::Model,
+    attempt: Attempt,
+) -> Result<(), DbErr> {
+    let txn = db.begin().await?;
+    let delivery = notification_delivery::ActiveModel {
+        id: sea_orm::ActiveValue::Set(nanoid!(21)),
+        subscriber_id: sea_orm::ActiveValue::Set(job.subscriber_id.to_owned()),
+        sensor_data_id: sea_orm::ActiveValue::Set(job.sensor_data_id.to_owned()),
+        attempt: sea_orm::ActiveValue::Set(job.attempts + 1),
+        succeeded: sea_orm::ActiveValue::Set(attempt.error.is_none()),
+        status_code: sea_orm::ActiveValue::Set(attempt.status_code.map(i32::from)),
+        latency_ms: sea_orm::ActiveValue::Set(
+            attempt.latency.as_millis().try_into().unwrap_or(i32::MAX),
+        ),
+        error: sea_orm::ActiveValue::Set(attempt.error.to_owned()),
+        ..Default::default()
+    };
+    NotificationDelivery::insert(delivery).exec(&txn).await?;
+
+    match attempt.error {
+        None => {
+            NotificationQueue::delete_by_id(&job.id).exec(&txn).await?;
+        }
+        Some(error) => retry_or_dead_letter(&txn, config, job, error).await?,
+    }
+    txn.commit().await
+}
+
+/// Outcome of one POST to a subscriber, kept in `notification_delivery`.
+struct Attempt {
+    status_code: Option<u16>,
+    latency: Duration,
+    /// `None` when the subscriber acknowledged the notification.
+    error: Option<String>,
+}
+
+async fn deliver(client: &Client, url: &str, secret: &str, payload: &Value) -> Attempt {
+    let started = Instant::now();
+    let (status_code, error) = match signed_post(client, url, secret, payload) {
+        Ok(request) => match request.await {
+            Ok(response) if response.status().is_success() => (Some(response.status()), None),
+            Ok(response) => (
+                Some(response.status()),
+                Some(format!("Subscriber responded with {}", response.status())),
+            ),
+            Err(e) => (None, Some(e.to_string())),
+        },
+        Err(e) => (None, Some(e)),
+    };
+    Attempt {
+        status_code: status_code.map(u16::from),
+        latency: started.elapsed(),
+        error,
+    }
+}
+
+async fn retry_or_dead_letter(
+    txn: &DatabaseTransaction,
+    config: &NotificationConfig,
+    job: notification_queue::Model,
+    error: String,
+) -> Result<(), DbErr> {
+    let attempts = job.attempts + 1;
+    if attempts >= config.max_attempts {
+        return dead_letter(txn, job, attempts, error).await;
+    }
+
+    let delay = TimeDelta::from_std(config.backoff(attempts)).unwrap_or(TimeDelta::days(1));
+    let mut job: notification_queue::ActiveModel = job.into();
+    job.attempts = sea_orm::ActiveValue::Set(attempts);
+    job.next_attempt_at = sea_orm::ActiveValue::Set(Utc::now().naive_utc() + delay);
+    job.last_error = sea_orm::ActiveValue::Set(Some(error));
+    job.update(txn).await?;
+    Ok(())
+}
+
+async fn dead_letter(
+    txn: &DatabaseTransaction,
+    job: notification_queue::Model,
+    attempts: i32,
+    error: String,
+) -> Result<(), DbErr> {
+    let dead_letter = dead_letter::ActiveModel {
+        id: sea_orm::ActiveValue::Set(nanoid!(21)),
+        subscriber_id: sea_orm::ActiveValue::Set(job.subscriber_id.to_owned()),
+        sensor_data_id: sea_orm::ActiveValue::Set(job.sensor_data_id.to_owned()),
+        notification_url: sea_orm::ActiveValue::Set(job.notification_url.to_owned()),
+        payload: sea_orm::ActiveValue::Set(job.payload.to_owned()),
+        attempts: sea_orm::ActiveValue::Set(attempts),
+        last_error: sea_orm::ActiveValue::Set(Some(error)),
+        ..Default::default()
+    };
+    DeadLetter::insert(dead_letter).exec(txn).await?;
+    NotificationQueue::delete_by_id(&job.id).exec(txn).await?;
+    Ok(())
+}
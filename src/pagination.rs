@@ -0,0 +1,353 @@
+//! Time-range queries over the readings of a container or a sensor with keyset
+//! pagination on `(<time field>, id)`, so deep pages cost the same as the
+//! first one.
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use sea_orm::{
+    ColumnTrait, Condition, ConnectionTrait, DbErr, EntityTrait, QueryFilter, QueryOrder,
+    QuerySelect, QueryTrait, Select, sea_query::SimpleExpr,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    entities::{prelude::*, *},
+    filter::DataFilter,
+};
+
+const DEFAULT_LIMIT: u64 = 100;
+const MAX_LIMIT: u64 = 1000;
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.f";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Order {
+    #[default]
+    Asc,
+    Desc,
+}
+
+impl Order {
+    fn as_str(self) -> &'static str {
+        match self {
+            Order::Asc => "asc",
+            Order::Desc => "desc",
+        }
+    }
+
+    fn parse(name: &str) -> Option<Self> {
+        [Order::Asc, Order::Desc]
+            .into_iter()
+            .find(|order| order.as_str() == name)
+    }
+}
+
+/// Which of a reading's timestamps a query ranges over: when it was stored or
+/// when the device says it was measured.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimeField {
+    #[default]
+    CreatedAt,
+    MeasuredAt,
+}
+
+impl TimeField {
+    pub fn column(self) -> sensor_data::Column {
+        match self {
+            TimeField::CreatedAt => sensor_data::Column::CreatedAt,
+            TimeField::MeasuredAt => sensor_data::Column::MeasuredAt,
+        }
+    }
+
+    pub fn of(self, entity: &sensor_data::Model) -> NaiveDateTime {
+        match self {
+            TimeField::CreatedAt => entity.created_at,
+            TimeField::MeasuredAt => entity.measured_at,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            TimeField::CreatedAt => "created_at",
+            TimeField::MeasuredAt => "measured_at",
+        }
+    }
+
+    fn parse(name: &str) -> Option<Self> {
+        [TimeField::CreatedAt, TimeField::MeasuredAt]
+            .into_iter()
+            .find(|time| time.as_str() == name)
+    }
+}
+
+/// Position of a reading when walking `time` in `order`, handed to clients as
+/// an opaque token. Cursors of one walk order by `(<time field>, id)`.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Cursor {
+    pub time: TimeField,
+    pub order: Order,
+    pub at: NaiveDateTime,
+    pub id: String,
+}
+
+impl Cursor {
+    pub fn of(time: TimeField, order: Order, entity: &sensor_data::Model) -> Self {
+        Self {
+            time,
+            order,
+            at: time.of(entity),
+            id: entity.id.to_owned(),
+        }
+    }
+
+    pub fn encode(&self) -> String {
+        hex::encode(format!(
+            "{}|{}|{}|{}",
+            self.time.as_str(),
+            self.order.as_str(),
+            self.at.format(TIMESTAMP_FORMAT),
+            self.id
+        ))
+    }
+
+    /// Readings strictly past the cursor in its walk.
+    pub fn past(&self) -> Condition {
+        let (time, at, id) = (self.time, self.at, self.id.to_owned());
+        let (at_past, id_past) = match self.order {
+            Order::Asc => (time.column().gt(at), sensor_data::Column::Id.gt(id)),
+            Order::Desc => (time.column().lt(at), sensor_data::Column::Id.lt(id)),
+        };
+        Condition::any()
+            .add(at_past)
+            .add(Condition::all().add(time.column().eq(at)).add(id_past))
+    }
+}
+
+impl TryFrom<String> for Cursor {
+    type Error = String;
+
+    fn try_from(token: String) -> Result<Self, Self::Error> {
+        let invalid = || format!("Invalid cursor `{}`", token);
+        let decoded = hex::decode(&token).map_err(|_| invalid())?;
+        let decoded = String::from_utf8(decoded).map_err(|_| invalid())?;
+        let mut parts = decoded.splitn(4, '|');
+        let mut next = || parts.next().ok_or_else(invalid);
+        let (time, order, at, id) = (next()?, next()?, next()?, next()?);
+        Ok(Self {
+            time: TimeField::parse(time).ok_or_else(invalid)?,
+            order: Order::parse(order).ok_or_else(invalid)?,
+            at: NaiveDateTime::parse_from_str(at, TIMESTAMP_FORMAT).map_err(|_| invalid())?,
+            id: id.to_owned(),
+        })
+    }
+}
+
+/// Whose readings a list endpoint returns.
+#[derive(Clone, Copy)]
+pub enum Source<'a> {
+    Container(&'a str),
+    /// Every container of the sensor.
+    Sensor(&'a str),
+}
+
+impl Source<'_> {
+    fn condition(self) -> SimpleExpr {
+        match self {
+            Source::Container(id) => sensor_data::Column::ContainerId.eq(id),
+            Source::Sensor(id) => sensor_data::Column::ContainerId.in_subquery(
+                DataContainer::find()
+                    .select_only()
+                    .column(data_container::Column::Id)
+                    .filter(data_container::Column::SensorId.eq(id))
+                    .into_query(),
+            ),
+        }
+    }
+}
+
+/// Query string of the sensor data list endpoints.
+#[derive(Deserialize)]
+pub struct SensorDataQuery {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub time: TimeField,
+    pub limit: Option<u64>,
+    #[serde(default)]
+    pub order: Order,
+    /// Only valid with the `time` and `order` of the page it came from, see
+    /// [`Self::check_cursor`].
+    pub cursor: Option<Cursor>,
+    /// Conditions on `data`, see [`DataFilter`].
+    pub filter: Option<DataFilter>,
+}
+
+impl SensorDataQuery {
+    /// Rejects a cursor from a page walked by another time field or order,
+    /// which would skip or repeat readings.
+    pub fn check_cursor(&self) -> Result<(), &'static str> {
+        match &self.cursor {
+            Some(cursor) if (cursor.time, cursor.order) != (self.time, self.order) => {
+                Err("The cursor belongs to a query with another time or order")
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Readings of `source` in range, matching the filter and past the cursor,
+    /// unordered.
+    pub fn filtered(&self, source: Source) -> Select<SensorData> {
+        let column = self.time.column();
+        SensorData::find()
+            .filter(source.condition())
+            .apply_if(self.from, |select, from| {
+                select.filter(column.gte(from.naive_utc()))
+            })
+            .apply_if(self.to, |select, to| {
+                select.filter(column.lt(to.naive_utc()))
+            })
+            .apply_if(self.filter.as_ref(), |select, filter| {
+                select.filter(filter.to_sql())
+            })
+            .apply_if(self.cursor.as_ref(), |select, cursor| {
+                select.filter(cursor.past())
+            })
+    }
+
+    /// [`Self::filtered`] in order, without a limit.
+    pub fn select(&self, source: Source) -> Select<SensorData> {
+        let column = self.time.column();
+        let select = self.filtered(source);
+        match self.order {
+            Order::Asc => select
+                .order_by_asc(column)
+                .order_by_asc(sensor_data::Column::Id),
+            Order::Desc => select
+                .order_by_desc(column)
+                .order_by_desc(sensor_data::Column::Id),
+        }
+    }
+
+    pub fn limit(&self) -> u64 {
+        self.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT)
+    }
+
+    pub async fn page<C: ConnectionTrait>(
+        &self,
+        db: &C,
+        source: Source<'_>,
+    ) -> Result<Page, DbErr> {
+        let limit = self.limit();
+        // One extra row tells whether another page follows.
+        let mut data = self.select(source).limit(limit + 1).all(db).await?;
+        let next_cursor = if data.len() as u64 > limit {
+            data.truncate(limit as usize);
+            data.last()
+                .map(|entity| Cursor::of(self.time, self.order, entity).encode())
+        } else {
+            None
+        };
+        Ok(Page { data, next_cursor })
+    }
+}
+
+#[derive(Serialize)]
+pub struct Page {
+    pub data: Vec<sensor_data::Model>,
+    /// Pass as `cursor` to fetch the next page; absent on the last one.
+    pub next_cursor: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    fn cursor(id: &str) -> Cursor {
+        Cursor {
+            time: TimeField::MeasuredAt,
+            order: Order::Desc,
+            at: NaiveDate::from_ymd_opt(2025, 3, 14)
+                .unwrap()
+                .and_hms_micro_opt(15, 9, 26, 535897)
+                .unwrap(),
+            id: id.to_owned(),
+        }
+    }
+
+    #[test]
+    fn cursor_round_trips() {
+        for id in ["V1StGXR8_Z5jdHi6B-myT", "with|pipe"] {
+            let cursor = cursor(id);
+            assert_eq!(Cursor::try_from(cursor.encode()), Ok(cursor));
+        }
+    }
+
+    #[test]
+    fn cursor_is_opaque() {
+        let token = cursor("abc").encode();
+        assert!(token.chars().all(|c| c.is_ascii_hexdigit()));
+    }
+
+    #[test]
+    fn rejects_invalid_cursors() {
+        for token in [
+            "not hex".to_owned(),
+            hex::encode("no separator"),
+            hex::encode("created_at|asc|yesterday|abc"),
+            hex::encode("updated_at|asc|2025-03-14T15:09:26|abc"),
+            hex::encode("created_at|up|2025-03-14T15:09:26|abc"),
+            hex::encode("2025-03-14T15:09:26|abc"),
+            hex::encode([0xff, 0xfe]),
+        ] {
+            assert!(Cursor::try_from(token).is_err());
+        }
+    }
+
+    #[test]
+    fn orders_by_time_then_id() {
+        assert!(cursor("a") < cursor("b"));
+        let mut later = cursor("a");
+        later.at += chrono::TimeDelta::microseconds(1);
+        assert!(cursor("b") < later);
+    }
+
+    fn query(time: TimeField, order: Order, cursor: Option<Cursor>) -> SensorDataQuery {
+        SensorDataQuery {
+            from: None,
+            to: None,
+            time,
+            limit: None,
+            order,
+            cursor,
+            filter: None,
+        }
+    }
+
+    #[test]
+    fn checks_cursor_against_query() {
+        let cursor = || Some(cursor("abc"));
+        assert!(
+            query(TimeField::CreatedAt, Order::Asc, None)
+                .check_cursor()
+                .is_ok()
+        );
+        assert!(
+            query(TimeField::MeasuredAt, Order::Desc, cursor())
+                .check_cursor()
+                .is_ok()
+        );
+        assert!(
+            query(TimeField::CreatedAt, Order::Desc, cursor())
+                .check_cursor()
+                .is_err()
+        );
+        assert!(
+            query(TimeField::MeasuredAt, Order::Asc, cursor())
+                .check_cursor()
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,191 @@
+use actix_web::{
+    Error,
+    error::{ErrorBadRequest, ErrorConflict, ErrorInternalServerError},
+    get, post,
+    web::{Data, Json, Path, Query, ServiceConfig},
+};
+use sea_orm::{
+    ColumnTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter, QueryOrder, QuerySelect,
+    QueryTrait, TransactionTrait,
+};
+use serde::Deserialize;
+
+use crate::{
+    AppState,
+    alarm::transition,
+    entities::{prelude::*, sea_orm_active_enums::AlarmState, *},
+    live::LiveEvent,
+};
+
+const DEFAULT_LIMIT: u64 = 100;
+const MAX_LIMIT: u64 = 1000;
+
+#[derive(Deserialize)]
+struct RAlarmParams {
+    id: String,
+}
+
+#[derive(Deserialize)]
+struct AlarmQuery {
+    rule_id: Option<String>,
+    container_id: Option<String>,
+    /// Alarms on any container of the sensor.
+    sensor_id: Option<String>,
+    state: Option<AlarmState>,
+    limit: Option<u64>,
+}
+
+/// Why a requested transition did not happen.
+enum TransitionError {
+    NotFound,
+    /// The alarm's current state does not allow it.
+    NotAllowed(AlarmState),
+    Db(DbErr),
+}
+
+impl From<DbErr> for TransitionError {
+    fn from(e: DbErr) -> Self {
+        TransitionError::Db(e)
+    }
+}
+
+/// Applies a transition requested through the API, with the alarm locked so
+/// it cannot race the rules engine.
+async fn transition_alarm(
+    db: &DatabaseConnection,
+    id: &str,
+    state: AlarmState,
+) -> Result<(alarm::Model, Option<LiveEvent>), TransitionError> {
+    let txn = db.begin().await?;
+    let Some(entity) = Alarm::find_by_id(id).lock_exclusive().one(&txn).await? else {
+        return Err(TransitionError::NotFound);
+    };
+    let allowed = match state {
+        AlarmState::Acknowledged => entity.state == AlarmState::Raised,
+        AlarmState::Cleared => entity.state != AlarmState::Cleared,
+        AlarmState::Raised => false,
+    };
+    if !allowed {
+        return Err(TransitionError::NotAllowed(entity.state));
+    }
+    let (entity, event) = transition(&txn, entity, state).await?;
+    txn.commit().await?;
+    Ok((entity, event))
+}
+
+async fn respond(
+    state: &AppState,
+    transition: Result<(alarm::Model, Option<LiveEvent>), TransitionError>,
+) -> Result<Json<alarm::Model>, Error> {
+    match transition {
+        Ok((entity, event)) => {
+            state.live.publish(event).await;
+            Ok(Json(entity))
+        }
+        Err(TransitionError::NotFound) => Err(ErrorBadRequest("Alarm not found")),
+        Err(TransitionError::NotAllowed(AlarmState::Cleared)) => {
+            Err(ErrorConflict("Alarm is already cleared"))
+        }
+        Err(TransitionError::NotAllowed(_)) => Err(ErrorConflict("Alarm is already acknowledged")),
+        Err(TransitionError::Db(e)) => {
+            eprintln!("Error updating alarm: {:?}", e);
+            Err(ErrorInternalServerError("Query failed"))
+        }
+    }
+}
+
+/// Most recently raised first.
+#[get("")]
+async fn get_alarms(
+    state: Data<AppState>,
+    query: Query<AlarmQuery>,
+) -> Result<Json<Vec<alarm::Model>>, Error> {
+    let AlarmQuery {
+        rule_id,
+        container_id,
+        sensor_id,
+        state: alarm_state,
+        limit,
+    } = query.into_inner();
+
+    match Alarm::find()
+        .apply_if(rule_id, |select, rule_id| {
+            select.filter(alarm::Column::RuleId.eq(rule_id))
+        })
+        .apply_if(container_id, |select, container_id| {
+            select.filter(alarm::Column::ContainerId.eq(container_id))
+        })
+        .apply_if(sensor_id, |select, sensor_id| {
+            select.filter(
+                alarm::Column::ContainerId.in_subquery(
+                    DataContainer::find()
+                        .select_only()
+                        .column(data_container::Column::Id)
+                        .filter(data_container::Column::SensorId.eq(sensor_id))
+                        .into_query(),
+                ),
+            )
+        })
+        .apply_if(alarm_state, |select, alarm_state| {
+            select.filter(alarm::Column::State.eq(alarm_state))
+        })
+        .order_by_desc(alarm::Column::RaisedAt)
+        .limit(limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT))
+        .all(&state.db)
+        .await
+    {
+        Ok(entities) => Ok(Json(entities)),
+        Err(e) => {
+            eprintln!("Error fetching alarms: {:?}", e);
+            Err(ErrorInternalServerError("Query failed"))
+        }
+    }
+}
+
+#[get("/{id}")]
+async fn get_alarm(
+    state: Data<AppState>,
+    params: Path<RAlarmParams>,
+) -> Result<Json<alarm::Model>, Error> {
+    let RAlarmParams { id } = params.into_inner();
+
+    match Alarm::find_by_id(&id).one(&state.db).await {
+        Ok(Some(entity)) => Ok(Json(entity)),
+        Ok(None) => Err(ErrorBadRequest("Alarm not found")),
+        Err(e) => {
+            eprintln!("Error fetching alarm: {:?}", e);
+            Err(ErrorInternalServerError("Query failed"))
+        }
+    }
+}
+
+/// Only raised alarms can be acknowledged. The alarm stays open until its
+/// rule clears it.
+#[post("/{id}/acknowledge")]
+async fn acknowledge_alarm(
+    state: Data<AppState>,
+    params: Path<RAlarmParams>,
+) -> Result<Json<alarm::Model>, Error> {
+    let RAlarmParams { id } = params.into_inner();
+    let transition = transition_alarm(&state.db, &id, AlarmState::Acknowledged).await;
+    respond(&state, transition).await
+}
+
+/// Clears an open alarm by hand, e.g. once the readings that would clear it
+/// will not come. A later breach raises a new alarm.
+#[post("/{id}/clear")]
+async fn clear_alarm(
+    state: Data<AppState>,
+    params: Path<RAlarmParams>,
+) -> Result<Json<alarm::Model>, Error> {
+    let RAlarmParams { id } = params.into_inner();
+    let transition = transition_alarm(&state.db, &id, AlarmState::Cleared).await;
+    respond(&state, transition).await
+}
+
+pub fn add_alarm_route(cfg: &mut ServiceConfig) {
+    cfg.service(get_alarms)
+        .service(get_alarm)
+        .service(acknowledge_alarm)
+        .service(clear_alarm);
+}
@@ -0,0 +1,366 @@
+use actix_web::{
+    Error, delete,
+    error::{ErrorBadRequest, ErrorInternalServerError},
+    get, patch, post,
+    web::{Data, Json, Path, Query, ServiceConfig},
+};
+use nanoid::nanoid;
+use redis::AsyncCommands;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter, QueryOrder,
+    SqlErr, TransactionTrait,
+};
+use serde::Deserialize;
+
+use crate::{
+    AppState,
+    alarm::{check_rule, reset, retire},
+    entities::{
+        prelude::*,
+        sea_orm_active_enums::{AlarmOperator, AlarmSeverity},
+        *,
+    },
+    live::LiveEvent,
+    utils::{get_redis_id, get_redis_set_options},
+};
+
+const PREFIX: &str = "AlarmRule";
+
+#[derive(Deserialize)]
+struct AlarmRuleCreate {
+    container_id: Option<String>,
+    sensor_id: Option<String>,
+    name: String,
+    /// Dotted path into the readings' `data`, e.g. `battery` or `gps.speed`.
+    field: String,
+    operator: AlarmOperator,
+    threshold: f64,
+    #[serde(default)]
+    duration_seconds: i64,
+    #[serde(default)]
+    hysteresis: f64,
+    severity: Option<AlarmSeverity>,
+    enabled: Option<bool>,
+}
+
+#[derive(Deserialize)]
+struct AlarmRuleUpdate {
+    name: Option<String>,
+    field: Option<String>,
+    operator: Option<AlarmOperator>,
+    threshold: Option<f64>,
+    duration_seconds: Option<i64>,
+    hysteresis: Option<f64>,
+    severity: Option<AlarmSeverity>,
+    enabled: Option<bool>,
+}
+
+#[derive(Deserialize)]
+struct RUDAlarmRuleParams {
+    id: String,
+}
+
+#[derive(Deserialize)]
+struct AlarmRuleQuery {
+    container_id: Option<String>,
+    sensor_id: Option<String>,
+}
+
+#[post("")]
+async fn create_alarm_rule(
+    state: Data<AppState>,
+    body: Json<AlarmRuleCreate>,
+) -> Result<Json<alarm_rule::Model>, Error> {
+    let AlarmRuleCreate {
+        container_id,
+        sensor_id,
+        name,
+        field,
+        operator,
+        threshold,
+        duration_seconds,
+        hysteresis,
+        severity,
+        enabled,
+    } = body.into_inner();
+
+    if container_id.is_some() == sensor_id.is_some() {
+        return Err(ErrorBadRequest(
+            "Exactly one of container_id or sensor_id is required",
+        ));
+    }
+    check_rule(&field, threshold, duration_seconds, hysteresis).map_err(ErrorBadRequest)?;
+
+    let new_alarm_rule = alarm_rule::ActiveModel {
+        id: sea_orm::ActiveValue::Set(nanoid!(10)),
+        container_id: sea_orm::ActiveValue::Set(container_id),
+        sensor_id: sea_orm::ActiveValue::Set(sensor_id),
+        name: sea_orm::ActiveValue::Set(name),
+        field: sea_orm::ActiveValue::Set(field),
+        operator: sea_orm::ActiveValue::Set(operator),
+        threshold: sea_orm::ActiveValue::Set(threshold),
+        duration_seconds: sea_orm::ActiveValue::Set(duration_seconds),
+        hysteresis: sea_orm::ActiveValue::Set(hysteresis),
+        severity: sea_orm::ActiveValue::Set(severity.unwrap_or(AlarmSeverity::Warning)),
+        enabled: sea_orm::ActiveValue::Set(enabled.unwrap_or(true)),
+        ..Default::default()
+    };
+
+    match new_alarm_rule.insert(&state.db).await {
+        Ok(entity) => {
+            let mut redis_conn = state
+                .redis
+                .get_multiplexed_tokio_connection()
+                .await
+                .unwrap();
+            let _: () = redis_conn
+                .set_options(
+                    get_redis_id(PREFIX, &entity.id),
+                    serde_json::to_string(&entity).unwrap(),
+                    get_redis_set_options(),
+                )
+                .await
+                .unwrap();
+            Ok(Json(entity))
+        }
+        Err(e) => match e.sql_err() {
+            Some(SqlErr::ForeignKeyConstraintViolation(_)) => {
+                Err(ErrorBadRequest("Can't find data container or sensor"))
+            }
+            _ => {
+                eprintln!("Error creating alarm rule: {:?}", e);
+                Err(ErrorInternalServerError("Query failed"))
+            }
+        },
+    }
+}
+
+/// Rules of a container or a sensor, or all of them.
+#[get("")]
+async fn get_alarm_rules(
+    state: Data<AppState>,
+    query: Query<AlarmRuleQuery>,
+) -> Result<Json<Vec<alarm_rule::Model>>, Error> {
+    let AlarmRuleQuery {
+        container_id,
+        sensor_id,
+    } = query.into_inner();
+
+    let mut select = AlarmRule::find();
+    if let Some(container_id) = container_id {
+        select = select.filter(alarm_rule::Column::ContainerId.eq(container_id));
+    }
+    if let Some(sensor_id) = sensor_id {
+        select = select.filter(alarm_rule::Column::SensorId.eq(sensor_id));
+    }
+    match select
+        .order_by_asc(alarm_rule::Column::CreateAt)
+        .all(&state.db)
+        .await
+    {
+        Ok(entities) => Ok(Json(entities)),
+        Err(e) => {
+            eprintln!("Error fetching alarm rules: {:?}", e);
+            Err(ErrorInternalServerError("Query failed"))
+        }
+    }
+}
+
+#[get("/{id}")]
+async fn get_alarm_rule(
+    state: Data<AppState>,
+    params: Path<RUDAlarmRuleParams>,
+) -> Result<Json<alarm_rule::Model>, Error> {
+    let RUDAlarmRuleParams { id } = params.into_inner();
+    let mut redis_conn = state
+        .redis
+        .get_multiplexed_tokio_connection()
+        .await
+        .unwrap();
+
+    if let Ok(cached_alarm_rule) = redis_conn.get::<_, String>(get_redis_id(PREFIX, &id)).await
+        && let Ok(entity) = serde_json::from_str::<alarm_rule::Model>(&cached_alarm_rule)
+    {
+        return Ok(Json(entity));
+    }
+
+    match AlarmRule::find_by_id(&id).one(&state.db).await {
+        Ok(Some(entity)) => {
+            let _: () = redis_conn
+                .set_options(
+                    get_redis_id(PREFIX, &id),
+                    serde_json::to_string(&entity).unwrap(),
+                    get_redis_set_options(),
+                )
+                .await
+                .unwrap();
+            Ok(Json(entity))
+        }
+        Ok(None) => Err(ErrorBadRequest("Alarm rule not found")),
+        Err(e) => {
+            eprintln!("Error fetching alarm rule: {:?}", e);
+            Err(ErrorInternalServerError("Query failed"))
+        }
+    }
+}
+
+/// Stores the rule and, when it is now disabled, clears its open alarms, in
+/// one transaction. A changed condition forgets the breach streaks counted
+/// under the old one.
+async fn update_and_retire(
+    db: &DatabaseConnection,
+    alarm_rule: alarm_rule::ActiveModel,
+    condition_changed: bool,
+) -> Result<(alarm_rule::Model, Vec<LiveEvent>), DbErr> {
+    let txn = db.begin().await?;
+    let entity = alarm_rule.update(&txn).await?;
+    let events = if entity.enabled {
+        if condition_changed {
+            reset(&txn, &entity.id).await?;
+        }
+        Vec::new()
+    } else {
+        retire(&txn, &entity.id).await?
+    };
+    txn.commit().await?;
+    Ok((entity, events))
+}
+
+/// Clears the rule's open alarms, then deletes it with them, in one
+/// transaction.
+async fn retire_and_delete(db: &DatabaseConnection, id: &str) -> Result<Vec<LiveEvent>, DbErr> {
+    let txn = db.begin().await?;
+    let events = retire(&txn, id).await?;
+    AlarmRule::delete_by_id(id).exec(&txn).await?;
+    txn.commit().await?;
+    Ok(events)
+}
+
+/// Changes apply from the next reading; open alarms stay open until it clears
+/// them under the updated rule, and a changed condition starts counting its
+/// duration over. Disabling the rule clears them at once.
+#[patch("/{id}")]
+async fn update_alarm_rule(
+    state: Data<AppState>,
+    params: Path<RUDAlarmRuleParams>,
+    body: Json<AlarmRuleUpdate>,
+) -> Result<Json<alarm_rule::Model>, Error> {
+    let RUDAlarmRuleParams { id } = params.into_inner();
+    let AlarmRuleUpdate {
+        name,
+        field,
+        operator,
+        threshold,
+        duration_seconds,
+        hysteresis,
+        severity,
+        enabled,
+    } = body.into_inner();
+
+    let alarm_rule = match AlarmRule::find_by_id(&id).one(&state.db).await {
+        Ok(Some(alarm_rule)) => alarm_rule,
+        Ok(None) => return Err(ErrorBadRequest("Alarm rule not found")),
+        Err(e) => {
+            eprintln!("Error fetching alarm rule: {:?}", e);
+            return Err(ErrorInternalServerError("Query failed"));
+        }
+    };
+    check_rule(
+        field.as_deref().unwrap_or(&alarm_rule.field),
+        threshold.unwrap_or(alarm_rule.threshold),
+        duration_seconds.unwrap_or(alarm_rule.duration_seconds),
+        hysteresis.unwrap_or(alarm_rule.hysteresis),
+    )
+    .map_err(ErrorBadRequest)?;
+    let condition_changed = field
+        .as_ref()
+        .is_some_and(|field| *field != alarm_rule.field)
+        || operator.is_some_and(|operator| operator != alarm_rule.operator)
+        || threshold.is_some_and(|threshold| threshold != alarm_rule.threshold)
+        || duration_seconds.is_some_and(|duration| duration != alarm_rule.duration_seconds);
+
+    let mut alarm_rule: alarm_rule::ActiveModel = alarm_rule.into();
+    if let Some(name) = name {
+        alarm_rule.name = sea_orm::ActiveValue::Set(name);
+    }
+    if let Some(field) = field {
+        alarm_rule.field = sea_orm::ActiveValue::Set(field);
+    }
+    if let Some(operator) = operator {
+        alarm_rule.operator = sea_orm::ActiveValue::Set(operator);
+    }
+    if let Some(threshold) = threshold {
+        alarm_rule.threshold = sea_orm::ActiveValue::Set(threshold);
+    }
+    if let Some(duration_seconds) = duration_seconds {
+        alarm_rule.duration_seconds = sea_orm::ActiveValue::Set(duration_seconds);
+    }
+    if let Some(hysteresis) = hysteresis {
+        alarm_rule.hysteresis = sea_orm::ActiveValue::Set(hysteresis);
+    }
+    if let Some(severity) = severity {
+        alarm_rule.severity = sea_orm::ActiveValue::Set(severity);
+    }
+    if let Some(enabled) = enabled {
+        alarm_rule.enabled = sea_orm::ActiveValue::Set(enabled);
+    }
+
+    match update_and_retire(&state.db, alarm_rule, condition_changed).await {
+        Ok((entity, events)) => {
+            state.live.publish(events).await;
+            let mut redis_conn = state
+                .redis
+                .get_multiplexed_tokio_connection()
+                .await
+                .unwrap();
+            let _: () = redis_conn
+                .set_options(
+                    get_redis_id(PREFIX, &id),
+                    serde_json::to_string(&entity).unwrap(),
+                    get_redis_set_options(),
+                )
+                .await
+                .unwrap();
+            Ok(Json(entity))
+        }
+        Err(e) => {
+            eprintln!("Error updating alarm rule: {:?}", e);
+            Err(ErrorInternalServerError("Query failed"))
+        }
+    }
+}
+
+/// Deletes the rule together with its alarms, clearing the open ones first so
+/// subscribers see them end.
+#[delete("/{id}")]
+async fn delete_alarm_rule(
+    state: Data<AppState>,
+    params: Path<RUDAlarmRuleParams>,
+) -> Result<&'static str, Error> {
+    let RUDAlarmRuleParams { id } = params.into_inner();
+
+    match retire_and_delete(&state.db, &id).await {
+        Ok(events) => {
+            state.live.publish(events).await;
+            let mut redis_conn = state
+                .redis
+                .get_multiplexed_tokio_connection()
+                .await
+                .unwrap();
+            let _: () = redis_conn.del(get_redis_id(PREFIX, &id)).await.unwrap();
+            Ok("Alarm rule deleted successfully")
+        }
+        Err(e) => {
+            eprintln!("Error deleting alarm rule: {:?}", e);
+            Err(ErrorInternalServerError("Query failed"))
+        }
+    }
+}
+
+pub fn add_alarm_rule_route(cfg: &mut ServiceConfig) {
+    cfg.service(create_alarm_rule)
+        .service(get_alarm_rules)
+        .service(get_alarm_rule)
+        .service(update_alarm_rule)
+        .service(delete_alarm_rule);
+}
@@ -1,12 +1,14 @@
 use actix_web::error::{ErrorBadRequest, ErrorInternalServerError};
-use actix_web::web::{Data, Json, Path, ServiceConfig};
+use actix_web::web::{Data, Json, Path, Query, ServiceConfig};
 use actix_web::{Error, delete, get, patch, post};
 use nanoid::nanoid;
 use redis::AsyncCommands;
 use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, SqlErr};
 use serde::Deserialize;
 
-use crate::{AppState, utils::get_redis_id};
+use crate::{
+    AppState, metadata::SensorQuery, routes::Subscriber::list_subscribers, utils::get_redis_id,
+};
 use crate::{
     entities::{prelude::*, *},
     utils::get_redis_set_options,
@@ -85,10 +87,10 @@ async fn get_application(
         .get_multiplexed_tokio_connection()
         .await
         .unwrap();
-    if let Ok(cached) = conn.get::<_, String>(get_redis_id(PREFIX, &id)).await {
-        if let Ok(app) = serde_json::from_str::<application::Model>(&cached) {
-            return Ok(Json(app));
-        }
+    if let Ok(cached) = conn.get::<_, String>(get_redis_id(PREFIX, &id)).await
+        && let Ok(app) = serde_json::from_str::<application::Model>(&cached)
+    {
+        return Ok(Json(app));
     }
 
     match Application::find_by_id(id).one(&state.db).await {
@@ -111,15 +113,19 @@ async fn get_application(
     }
 }
 
+/// The application's sensors, filtered by metadata, e.g.
+/// `?kind=air_quality&tags=roof,north&bbox=4.8,52.3,5.0,52.4`.
 #[get("/{id}/sensors")]
 async fn get_application_sensors(
     state: Data<AppState>,
     params: Path<RUDApplicationParams>,
+    query: Query<SensorQuery>,
 ) -> Result<Json<Vec<sensor::Model>>, Error> {
     let RUDApplicationParams { id } = params.into_inner();
 
     match Sensor::find()
         .filter(sensor::Column::ApplicationId.eq(id))
+        .filter(query.to_sql())
         .all(&state.db)
         .await
     {
@@ -131,6 +137,15 @@ async fn get_application_sensors(
     }
 }
 
+#[get("/{id}/subscribers")]
+async fn get_application_subscribers(
+    state: Data<AppState>,
+    params: Path<RUDApplicationParams>,
+) -> Result<Json<Vec<subscribers::Model>>, Error> {
+    let RUDApplicationParams { id } = params.into_inner();
+    list_subscribers(&state, subscribers::Column::ApplicationId, &id).await
+}
+
 #[patch("/{id}")]
 async fn update_application(
     state: Data<AppState>,
@@ -199,6 +214,7 @@ pub fn add_application_route(cfg: &mut ServiceConfig) {
     cfg.service(add_application)
         .service(get_application)
         .service(get_application_sensors)
+        .service(get_application_subscribers)
         .service(update_application)
         .service(delete_application);
 }
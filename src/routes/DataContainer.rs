@@ -1,25 +1,93 @@
 use actix_web::{
-    Error, delete,
+    Error, HttpRequest, HttpResponse, delete,
     error::{ErrorBadRequest, ErrorInternalServerError},
-    get, post,
-    web::{Data, Json, Path, ServiceConfig},
+    get,
+    http::header,
+    patch, post,
+    web::{self, Bytes, Data, Json, Path, PayloadConfig, Query, ServiceConfig, block},
 };
+use std::sync::LazyLock;
+
 use nanoid::nanoid;
-use redis::AsyncCommands;
-use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, SqlErr};
+use redis::{AsyncCommands, RedisResult, Script, aio::MultiplexedConnection};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter, QueryOrder,
+    QuerySelect, QueryTrait, SqlErr, TransactionTrait,
+};
 use serde::Deserialize;
+use serde_json::Value;
 
 use crate::entities::{prelude::*, *};
 use crate::{
     AppState,
-    utils::{get_redis_id, get_redis_set_options},
+    aggregation::{AggregateQuery, BucketStats},
+    archive::{self, ArchiveQuery, ReadError, read_range},
+    capacity::evict,
+    events::record,
+    export::{Format, data_columns, export_stream},
+    import::{self, ImportQuery, ImportSummary, Mapping},
+    live::{LiveEvent, sse::sensor_data_stream},
+    notification::{envelope::EventType, scope::Resource},
+    pagination::{SensorDataQuery, Source},
+    routes::{SensorData::uncache, Subscriber::list_subscribers},
+    schema,
+    utils::{REDIS_TTL_SECONDS, double_option, env_or, get_redis_id, get_redis_set_options},
 };
 
 const PREFIX: &str = "DataContainer";
+/// Cache of each container's newest reading, refreshed on every insert.
+pub const LATEST_PREFIX: &str = "DataContainerLatest";
+
+/// Sets the latest-reading key unless it holds a reading measured later, or
+/// at the same time with a greater id. Timestamps are serialized the same
+/// way, so they compare as strings.
+static CACHE_LATEST: LazyLock<Script> = LazyLock::new(|| {
+    Script::new(
+        r"
+        local cached = redis.call('GET', KEYS[1])
+        if cached then
+            local ok, reading = pcall(cjson.decode, cached)
+            if ok and type(reading.measured_at) == 'string'
+                and (reading.measured_at > ARGV[2]
+                    or (reading.measured_at == ARGV[2] and reading.id >= ARGV[3])) then
+                return 0
+            end
+        end
+        redis.call('SET', KEYS[1], ARGV[1], 'EX', ARGV[4])
+        return 1
+        ",
+    )
+});
+
+/// Largest CSV or NDJSON body accepted by the import endpoint, in bytes.
+const DEFAULT_MAX_IMPORT_SIZE: usize = 64 * 1024 * 1024;
 
 #[derive(Deserialize)]
 struct DataContainerCreate {
     pub sensor_id: String,
+    pub max_nr_of_instances: Option<i64>,
+    pub max_byte_size: Option<i64>,
+    pub max_instance_age: Option<i64>,
+    #[serde(default)]
+    pub notify_on_eviction: bool,
+    pub schema: Option<Value>,
+    pub retention_period: Option<i64>,
+}
+
+/// Omitted fields are left alone; a `null` limit removes it.
+#[derive(Deserialize)]
+struct DataContainerUpdate {
+    #[serde(default, deserialize_with = "double_option")]
+    pub max_nr_of_instances: Option<Option<i64>>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub max_byte_size: Option<Option<i64>>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub max_instance_age: Option<Option<i64>>,
+    pub notify_on_eviction: Option<bool>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub schema: Option<Option<Value>>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub retention_period: Option<Option<i64>>,
 }
 
 #[derive(Deserialize)]
@@ -27,21 +95,90 @@ struct RDDataContainerParams {
     id: String,
 }
 
+fn check_limits(limits: &[Option<i64>]) -> Result<(), Error> {
+    if limits.iter().flatten().any(|limit| *limit <= 0) {
+        return Err(ErrorBadRequest("Capacity limits must be positive"));
+    }
+    Ok(())
+}
+
+/// Stores new limits and evicts what they no longer allow in one transaction.
+/// Returns the container, the evicted ids and the live events.
+async fn update_and_evict(
+    db: &DatabaseConnection,
+    container: data_container::ActiveModel,
+) -> Result<(data_container::Model, Vec<String>, Vec<LiveEvent>), DbErr> {
+    let txn = db.begin().await?;
+    let entity = container.update(&txn).await?;
+    let (evicted, events) = evict(&txn, &entity).await?;
+    txn.commit().await?;
+    Ok((entity, evicted, events))
+}
+
+async fn insert_and_enqueue(
+    db: &DatabaseConnection,
+    new_data_container: data_container::ActiveModel,
+) -> Result<(data_container::Model, Option<LiveEvent>), DbErr> {
+    let txn = db.begin().await?;
+    let entity = new_data_container.insert(&txn).await?;
+    let resource = Resource::DataContainer(&entity);
+    let event = record(&txn, EventType::DataContainerCreated, resource).await?;
+    txn.commit().await?;
+    Ok((entity, event))
+}
+
+/// Deletes a container and queues `data_container.deleted` in one transaction.
+async fn delete_and_enqueue(db: &DatabaseConnection, id: &str) -> Result<Option<LiveEvent>, DbErr> {
+    let txn = db.begin().await?;
+    let mut event = None;
+    if let Some(entity) = DataContainer::find_by_id(id).one(&txn).await? {
+        let resource = Resource::DataContainer(&entity);
+        event = record(&txn, EventType::DataContainerDeleted, resource).await?;
+        DataContainer::delete_by_id(id).exec(&txn).await?;
+    }
+    txn.commit().await?;
+    Ok(event)
+}
+
 #[post("")]
 async fn create_data_container(
     state: Data<AppState>,
     body: Json<DataContainerCreate>,
 ) -> Result<Json<data_container::Model>, Error> {
-    let DataContainerCreate { sensor_id } = body.into_inner();
+    let DataContainerCreate {
+        sensor_id,
+        max_nr_of_instances,
+        max_byte_size,
+        max_instance_age,
+        notify_on_eviction,
+        schema,
+        retention_period,
+    } = body.into_inner();
+    check_limits(&[
+        max_nr_of_instances,
+        max_byte_size,
+        max_instance_age,
+        retention_period,
+    ])?;
+    if let Some(schema) = &schema {
+        schema::check(schema).map_err(ErrorBadRequest)?;
+    }
 
     let new_data_container = data_container::ActiveModel {
         id: sea_orm::ActiveValue::Set(nanoid!(10)),
         sensor_id: sea_orm::ActiveValue::Set(sensor_id.to_owned()),
+        max_nr_of_instances: sea_orm::ActiveValue::Set(max_nr_of_instances),
+        max_byte_size: sea_orm::ActiveValue::Set(max_byte_size),
+        max_instance_age: sea_orm::ActiveValue::Set(max_instance_age),
+        notify_on_eviction: sea_orm::ActiveValue::Set(notify_on_eviction),
+        schema: sea_orm::ActiveValue::Set(schema),
+        retention_period: sea_orm::ActiveValue::Set(retention_period),
         ..Default::default()
     };
 
-    match new_data_container.insert(&state.db).await {
-        Ok(entity) => {
+    match insert_and_enqueue(&state.db, new_data_container).await {
+        Ok((entity, event)) => {
+            state.live.publish(event).await;
             let mut redis_conn = state
                 .redis
                 .get_multiplexed_tokio_connection()
@@ -81,10 +218,10 @@ async fn get_data_container(
         .await
         .unwrap();
 
-    if let Ok(cached_data) = redis_conn.get::<_, String>(get_redis_id(PREFIX, &id)).await {
-        if let Ok(entity) = serde_json::from_str::<data_container::Model>(&cached_data) {
-            return Ok(Json(entity));
-        }
+    if let Ok(cached_data) = redis_conn.get::<_, String>(get_redis_id(PREFIX, &id)).await
+        && let Ok(entity) = serde_json::from_str::<data_container::Model>(&cached_data)
+    {
+        return Ok(Json(entity));
     }
 
     match DataContainer::find_by_id(&id).one(&state.db).await {
@@ -107,18 +244,328 @@ async fn get_data_container(
     }
 }
 
+#[patch("/{id}")]
+async fn update_data_container(
+    state: Data<AppState>,
+    params: Path<RDDataContainerParams>,
+    body: Json<DataContainerUpdate>,
+) -> Result<Json<data_container::Model>, Error> {
+    let RDDataContainerParams { id } = params.into_inner();
+    let DataContainerUpdate {
+        max_nr_of_instances,
+        max_byte_size,
+        max_instance_age,
+        notify_on_eviction,
+        schema,
+        retention_period,
+    } = body.into_inner();
+    check_limits(&[
+        max_nr_of_instances.flatten(),
+        max_byte_size.flatten(),
+        max_instance_age.flatten(),
+        retention_period.flatten(),
+    ])?;
+    if let Some(Some(schema)) = &schema {
+        schema::check(schema).map_err(ErrorBadRequest)?;
+    }
+
+    let mut entity: data_container::ActiveModel =
+        match DataContainer::find_by_id(&id).one(&state.db).await {
+            Ok(Some(entity)) => entity.into(),
+            Ok(None) => return Err(ErrorBadRequest("Data container not found")),
+            Err(e) => {
+                eprintln!("Error fetching data container: {:?}", e);
+                return Err(ErrorInternalServerError("Query failed"));
+            }
+        };
+    if let Some(max_nr_of_instances) = max_nr_of_instances {
+        entity.max_nr_of_instances = sea_orm::ActiveValue::Set(max_nr_of_instances);
+    }
+    if let Some(max_byte_size) = max_byte_size {
+        entity.max_byte_size = sea_orm::ActiveValue::Set(max_byte_size);
+    }
+    if let Some(max_instance_age) = max_instance_age {
+        entity.max_instance_age = sea_orm::ActiveValue::Set(max_instance_age);
+    }
+    if let Some(notify_on_eviction) = notify_on_eviction {
+        entity.notify_on_eviction = sea_orm::ActiveValue::Set(notify_on_eviction);
+    }
+    if let Some(schema) = schema {
+        entity.schema = sea_orm::ActiveValue::Set(schema);
+    }
+    if let Some(retention_period) = retention_period {
+        entity.retention_period = sea_orm::ActiveValue::Set(retention_period);
+    }
+
+    match update_and_evict(&state.db, entity).await {
+        Ok((updated_entity, evicted, events)) => {
+            state.live.publish(events).await;
+            let mut redis_conn = state
+                .redis
+                .get_multiplexed_tokio_connection()
+                .await
+                .unwrap();
+            if !evicted.is_empty() {
+                uncache(&mut redis_conn, &evicted).await.unwrap();
+                let _: () = redis_conn
+                    .del(get_redis_id(LATEST_PREFIX, &id))
+                    .await
+                    .unwrap();
+            }
+            let _: () = redis_conn
+                .set_options(
+                    get_redis_id(PREFIX, &id),
+                    serde_json::to_string(&updated_entity).unwrap(),
+                    get_redis_set_options(),
+                )
+                .await
+                .unwrap();
+            Ok(Json(updated_entity))
+        }
+        Err(e) => {
+            eprintln!("Error updating data container: {:?}", e);
+            Err(ErrorInternalServerError("Failed to update data container"))
+        }
+    }
+}
+
+/// A page of readings as JSON, or with `Accept: text/csv` or
+/// `application/x-ndjson` every matching reading streamed as an export. An
+/// export is only limited when `limit` is given.
 #[get("/{id}/sensor_data")]
 async fn get_sensor_data(
     state: Data<AppState>,
     params: Path<RDDataContainerParams>,
-) -> Result<Json<Vec<sensor_data::Model>>, Error> {
+    query: Query<SensorDataQuery>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let RDDataContainerParams { id } = params.into_inner();
+    list_sensor_data(&state, Source::Container(&id), &query, &req).await
+}
+
+/// Shared by the container and sensor list endpoints.
+pub async fn list_sensor_data(
+    state: &AppState,
+    source: Source<'_>,
+    query: &SensorDataQuery,
+    req: &HttpRequest,
+) -> Result<HttpResponse, Error> {
+    query.check_cursor().map_err(ErrorBadRequest)?;
+    let Some(format) = Format::from_accept(req) else {
+        return match query.page(&state.db, source).await {
+            Ok(page) => Ok(HttpResponse::Ok().json(page)),
+            Err(e) => {
+                eprintln!("Error fetching sensor data: {:?}", e);
+                Err(ErrorInternalServerError("Query failed"))
+            }
+        };
+    };
+
+    let select = query
+        .select(source)
+        .apply_if(query.limit, |select, _| select.limit(query.limit()));
+    let columns = match format {
+        Format::Csv => match data_columns(&state.db, select.clone()).await {
+            Ok(columns) => columns,
+            Err(e) => {
+                eprintln!("Error fetching sensor data columns: {:?}", e);
+                return Err(ErrorInternalServerError("Query failed"));
+            }
+        },
+        Format::Ndjson => Vec::new(),
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type(format.content_type())
+        .streaming(export_stream(state.db.clone(), select, format, columns)))
+}
+
+/// Backfills readings from a `text/csv` or `application/x-ndjson` body; see
+/// `ImportQuery` for the column mapping. No notifications are sent.
+/// Registered with its own `PayloadConfig` by `add_data_container_routes`.
+async fn import_sensor_data(
+    state: Data<AppState>,
+    params: Path<RDDataContainerParams>,
+    query: Query<ImportQuery>,
+    req: HttpRequest,
+    body: Bytes,
+) -> Result<Json<ImportSummary>, Error> {
     let RDDataContainerParams { id } = params.into_inner();
+    let format = Format::from_content_type(&req)
+        .ok_or_else(|| ErrorBadRequest("Expected a text/csv or application/x-ndjson body"))?;
+    let mapping =
+        Mapping::parse(&query.timestamp, query.fields.as_deref()).map_err(ErrorBadRequest)?;
+
+    let container = match DataContainer::find_by_id(&id).one(&state.db).await {
+        Ok(Some(container)) => container,
+        Ok(None) => return Err(ErrorBadRequest("Data container not found")),
+        Err(e) => {
+            eprintln!("Error fetching data container: {:?}", e);
+            return Err(ErrorInternalServerError("Query failed"));
+        }
+    };
+    let readings = block(move || import::parse(&body, format, &mapping, &container))
+        .await
+        .map_err(|e| {
+            eprintln!("Error parsing sensor data import: {:?}", e);
+            ErrorInternalServerError("Import failed")
+        })?
+        .map_err(|e| e.into_response())?;
+
+    match import::store(&state.db, &id, readings).await {
+        Ok(imported) => {
+            let mut redis_conn = state
+                .redis
+                .get_multiplexed_tokio_connection()
+                .await
+                .unwrap();
+            let _: () = redis_conn
+                .del(get_redis_id(LATEST_PREFIX, &id))
+                .await
+                .unwrap();
+            Ok(Json(ImportSummary { imported }))
+        }
+        Err(e) => {
+            eprintln!("Error importing sensor data: {:?}", e);
+            Err(ErrorInternalServerError("Query failed"))
+        }
+    }
+}
+
+/// Readings archived from the container and created in `[from, to)`, as a
+/// single Parquet file of at most `ARCHIVE_MAX_ROWS` readings.
+#[get("/{id}/archive")]
+async fn get_archive(
+    state: Data<AppState>,
+    params: Path<RDDataContainerParams>,
+    query: Query<ArchiveQuery>,
+) -> Result<HttpResponse, Error> {
+    let RDDataContainerParams { id } = params.into_inner();
+    let ArchiveQuery { from, to } = query.into_inner();
+    if from >= to {
+        return Err(ErrorBadRequest("`from` must be before `to`"));
+    }
+
+    match DataContainer::find_by_id(&id).one(&state.db).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return Err(ErrorBadRequest("Data container not found")),
+        Err(e) => {
+            eprintln!("Error fetching data container: {:?}", e);
+            return Err(ErrorInternalServerError("Query failed"));
+        }
+    }
+
+    let config = state.archive.clone();
+    let max_rows = config.max_rows;
+    let filename = format!(
+        "{}_{}_{}.parquet",
+        id,
+        from.format("%Y%m%dT%H%M%SZ"),
+        to.format("%Y%m%dT%H%M%SZ")
+    );
+    let archive = block(move || {
+        let entities = read_range(&config, &id, from.naive_utc(), to.naive_utc())?;
+        archive::encode(&entities).map_err(ReadError::from)
+    })
+    .await;
+
+    match archive {
+        Ok(Ok(archive)) => Ok(HttpResponse::Ok()
+            .content_type(archive::CONTENT_TYPE)
+            .insert_header((
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", filename),
+            ))
+            .body(archive)),
+        Ok(Err(ReadError::TooLarge)) => Err(ErrorBadRequest(format!(
+            "The range holds more than {} archived readings, request a shorter one",
+            max_rows
+        ))),
+        Ok(Err(ReadError::Parquet(e))) => {
+            eprintln!("Error reading sensor data archive: {:?}", e);
+            Err(ErrorInternalServerError("Query failed"))
+        }
+        Err(e) => {
+            eprintln!("Error reading sensor data archive: {:?}", e);
+            Err(ErrorInternalServerError("Query failed"))
+        }
+    }
+}
+
+#[get("/{id}/aggregate")]
+async fn get_aggregate(
+    state: Data<AppState>,
+    params: Path<RDDataContainerParams>,
+    query: Query<AggregateQuery>,
+) -> Result<Json<Vec<BucketStats>>, Error> {
+    let RDDataContainerParams { id } = params.into_inner();
+    let path = query.path().map_err(ErrorBadRequest)?;
+
+    match query.run(&state.db, &id, path).await {
+        Ok(buckets) => Ok(Json(buckets)),
+        Err(e) => {
+            eprintln!("Error aggregating sensor data: {:?}", e);
+            Err(ErrorInternalServerError("Query failed"))
+        }
+    }
+}
+
+/// Caches `entity` as its container's latest reading unless a newer one, by
+/// `measured_at`, is already cached, so concurrent or late writes cannot
+/// roll the cache back.
+pub async fn cache_latest_reading(
+    redis_conn: &mut MultiplexedConnection,
+    entity: &sensor_data::Model,
+) -> RedisResult<()> {
+    let Value::String(measured_at) = serde_json::to_value(entity.measured_at).unwrap() else {
+        unreachable!("timestamps serialize as strings");
+    };
+    let _: i64 = CACHE_LATEST
+        .key(get_redis_id(LATEST_PREFIX, &entity.container_id))
+        .arg(serde_json::to_string(entity).unwrap())
+        .arg(measured_at)
+        .arg(&entity.id)
+        .arg(REDIS_TTL_SECONDS)
+        .invoke_async(redis_conn)
+        .await?;
+    Ok(())
+}
+
+/// The container's reading measured last.
+#[get("/{id}/latest")]
+async fn get_latest_sensor_data(
+    state: Data<AppState>,
+    params: Path<RDDataContainerParams>,
+) -> Result<Json<sensor_data::Model>, Error> {
+    let RDDataContainerParams { id } = params.into_inner();
+    let mut redis_conn = state
+        .redis
+        .get_multiplexed_tokio_connection()
+        .await
+        .unwrap();
+
+    if let Ok(cached_data) = redis_conn
+        .get::<_, String>(get_redis_id(LATEST_PREFIX, &id))
+        .await
+        && let Ok(entity) = serde_json::from_str::<sensor_data::Model>(&cached_data)
+    {
+        return Ok(Json(entity));
+    }
+
     match SensorData::find()
-        .filter(sensor_data::Column::ContainerId.eq(id))
-        .all(&state.db)
+        .filter(sensor_data::Column::ContainerId.eq(&id))
+        .order_by_desc(sensor_data::Column::MeasuredAt)
+        .order_by_desc(sensor_data::Column::Id)
+        .one(&state.db)
         .await
     {
-        Ok(entities) => Ok(Json(entities)),
+        Ok(Some(entity)) => {
+            cache_latest_reading(&mut redis_conn, &entity)
+                .await
+                .unwrap();
+            Ok(Json(entity))
+        }
+        Ok(None) => Err(ErrorBadRequest("Sensor data not found")),
         Err(e) => {
             eprintln!("Error fetching sensor data: {:?}", e);
             Err(ErrorInternalServerError("Query failed"))
@@ -126,25 +573,85 @@ async fn get_sensor_data(
     }
 }
 
-#[get("/{id}/subscribers")]
-async fn get_subscribers(
+#[get("/{id}/oldest")]
+async fn get_oldest_sensor_data(
     state: Data<AppState>,
     params: Path<RDDataContainerParams>,
-) -> Result<Json<Vec<subscribers::Model>>, Error> {
+) -> Result<Json<sensor_data::Model>, Error> {
     let RDDataContainerParams { id } = params.into_inner();
-    match Subscribers::find()
-        .filter(subscribers::Column::ContainerId.eq(id))
-        .all(&state.db)
+    match SensorData::find()
+        .filter(sensor_data::Column::ContainerId.eq(id))
+        .order_by_asc(sensor_data::Column::CreatedAt)
+        .order_by_asc(sensor_data::Column::Id)
+        .one(&state.db)
         .await
     {
-        Ok(entities) => Ok(Json(entities)),
+        Ok(Some(entity)) => Ok(Json(entity)),
+        Ok(None) => Err(ErrorBadRequest("Sensor data not found")),
         Err(e) => {
-            eprintln!("Error fetching subscribers: {:?}", e);
+            eprintln!("Error fetching sensor data: {:?}", e);
             Err(ErrorInternalServerError("Query failed"))
         }
     }
 }
 
+#[get("/{id}/stream")]
+async fn stream_sensor_data(
+    state: Data<AppState>,
+    params: Path<RDDataContainerParams>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let RDDataContainerParams { id } = params.into_inner();
+    let receiver = state.live.subscribe();
+
+    match DataContainer::find_by_id(&id).one(&state.db).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return Err(ErrorBadRequest("Data container not found")),
+        Err(e) => {
+            eprintln!("Error fetching data container: {:?}", e);
+            return Err(ErrorInternalServerError("Query failed"));
+        }
+    }
+
+    let last_event_id = req
+        .headers()
+        .get("Last-Event-ID")
+        .and_then(|value| value.to_str().ok());
+    let resume_from = match last_event_id {
+        Some(last_event_id) => match SensorData::find_by_id(last_event_id)
+            .filter(sensor_data::Column::ContainerId.eq(&id))
+            .one(&state.db)
+            .await
+        {
+            Ok(entity) => entity,
+            Err(e) => {
+                eprintln!("Error fetching sensor data: {:?}", e);
+                return Err(ErrorInternalServerError("Query failed"));
+            }
+        },
+        None => None,
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header((header::CACHE_CONTROL, "no-cache"))
+        .streaming(sensor_data_stream(
+            state.db.clone(),
+            receiver,
+            id,
+            resume_from,
+        )))
+}
+
+#[get("/{id}/subscribers")]
+async fn get_subscribers(
+    state: Data<AppState>,
+    params: Path<RDDataContainerParams>,
+) -> Result<Json<Vec<subscribers::Model>>, Error> {
+    let RDDataContainerParams { id } = params.into_inner();
+    list_subscribers(&state, subscribers::Column::ContainerId, &id).await
+}
+
 #[delete("/{id}")]
 async fn delete_data_container(
     state: Data<AppState>,
@@ -152,14 +659,19 @@ async fn delete_data_container(
 ) -> Result<&'static str, Error> {
     let RDDataContainerParams { id } = params.into_inner();
 
-    match DataContainer::delete_by_id(&id).exec(&state.db).await {
-        Ok(_) => {
+    match delete_and_enqueue(&state.db, &id).await {
+        Ok(event) => {
+            state.live.publish(event).await;
             let mut redis_conn = state
                 .redis
                 .get_multiplexed_tokio_connection()
                 .await
                 .unwrap();
             let _: () = redis_conn.del(get_redis_id(PREFIX, &id)).await.unwrap();
+            let _: () = redis_conn
+                .del(get_redis_id(LATEST_PREFIX, &id))
+                .await
+                .unwrap();
             Ok("Data container deleted")
         }
         Err(e) => {
@@ -172,7 +684,21 @@ async fn delete_data_container(
 pub fn add_data_container_routes(cfg: &mut ServiceConfig) {
     cfg.service(create_data_container)
         .service(get_data_container)
+        .service(update_data_container)
         .service(get_sensor_data)
+        .service(
+            web::resource("/{id}/import")
+                .app_data(PayloadConfig::new(env_or(
+                    "IMPORT_MAX_SIZE_BYTES",
+                    DEFAULT_MAX_IMPORT_SIZE,
+                )))
+                .route(web::post().to(import_sensor_data)),
+        )
+        .service(get_archive)
+        .service(get_aggregate)
+        .service(get_latest_sensor_data)
+        .service(get_oldest_sensor_data)
+        .service(stream_sensor_data)
         .service(get_subscribers)
         .service(delete_data_container);
 }
@@ -1,5 +1,6 @@
 use crate::AppState;
 use crate::entities::{prelude::*, *};
+use crate::routes::Subscriber::list_subscribers;
 use crate::utils::{get_redis_id, get_redis_set_options};
 use actix_web::{
     Error, delete,
@@ -83,10 +84,10 @@ async fn get_home(
         .get_multiplexed_tokio_connection()
         .await
         .unwrap();
-    if let Ok(cached_home) = redis_conn.get::<_, String>(get_redis_id(PREFIX, &id)).await {
-        if let Ok(home) = serde_json::from_str::<home::Model>(&cached_home) {
-            return Ok(Json(home));
-        }
+    if let Ok(cached_home) = redis_conn.get::<_, String>(get_redis_id(PREFIX, &id)).await
+        && let Ok(home) = serde_json::from_str::<home::Model>(&cached_home)
+    {
+        return Ok(Json(home));
     }
 
     match Home::find_by_id(&id).one(&state.db).await {
@@ -130,6 +131,15 @@ async fn get_home_application(
     }
 }
 
+#[get("/{id}/subscribers")]
+async fn get_home_subscribers(
+    state: Data<AppState>,
+    params: Path<HomeParams>,
+) -> Result<Json<Vec<subscribers::Model>>, Error> {
+    let HomeParams { id } = params.into_inner();
+    list_subscribers(&state, subscribers::Column::HomeId, &id).await
+}
+
 #[patch("/{id}")]
 async fn update_home(
     state: Data<AppState>,
@@ -166,7 +176,7 @@ async fn update_home(
                 }
             }
         }
-        Ok(None) => return Err(ErrorBadRequest("Home not found")),
+        Ok(None) => Err(ErrorBadRequest("Home not found")),
         Err(e) => {
             eprintln!("Error fetching home: {:?}", e);
             Err(ErrorInternalServerError("Failed to fetch home"))
@@ -203,6 +213,7 @@ pub fn add_home_route(cfg: &mut ServiceConfig) {
         .service(get_home)
         .service(get_homes)
         .service(get_home_application)
+        .service(get_home_subscribers)
         .service(update_home)
         .service(delete_home);
 }
@@ -1,31 +1,71 @@
 use actix_web::{
-    Error, delete,
+    Error, HttpRequest, HttpResponse, delete,
     error::{ErrorBadRequest, ErrorInternalServerError},
     get, patch, post,
-    web::{Data, Json, Path, ServiceConfig},
+    web::{Data, Json, Path, Query, ServiceConfig},
 };
 use nanoid::nanoid;
 use redis::AsyncCommands;
-use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, SqlErr};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, DbErr, EntityTrait, ModelTrait, QueryFilter,
+    SqlErr, TransactionTrait,
+};
 use serde::Deserialize;
+use serde_json::Value;
 
 use crate::entities::{prelude::*, *};
 use crate::{
     AppState,
-    utils::{get_redis_id, get_redis_set_options},
+    events::record,
+    live::LiveEvent,
+    metadata::{check_location, check_units, normalize_tags},
+    notification::{envelope::EventType, scope::Resource},
+    pagination::{SensorDataQuery, Source},
+    routes::{DataContainer::list_sensor_data, Subscriber::list_subscribers},
+    utils::{double_option, get_redis_id, get_redis_set_options},
 };
 
-const PREFIX: &str = "Sensor";
+pub const PREFIX: &str = "Sensor";
 
 #[derive(Deserialize)]
 struct SensorCreate {
     pub name: String,
     pub application_id: String,
+    /// Seconds the sensor may stay silent before it is marked offline.
+    pub reporting_interval: Option<i64>,
+    pub kind: Option<String>,
+    pub units: Option<Value>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub manufacturer: Option<String>,
+    pub model_name: Option<String>,
+    pub serial_number: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
 }
 
+/// Omitted fields are left alone; `null` clears a field, and a `null`
+/// reporting interval stops the sensor's offline detection.
 #[derive(Deserialize)]
 struct SensorUpdate {
-    pub name: String,
+    pub name: Option<String>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub reporting_interval: Option<Option<i64>>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub kind: Option<Option<String>>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub units: Option<Option<Value>>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub latitude: Option<Option<f64>>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub longitude: Option<Option<f64>>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub manufacturer: Option<Option<String>>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub model_name: Option<Option<String>>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub serial_number: Option<Option<String>>,
+    pub tags: Option<Vec<String>>,
 }
 
 #[derive(Deserialize)]
@@ -33,6 +73,41 @@ struct RUDSensorParams {
     id: String,
 }
 
+fn check_reporting_interval(reporting_interval: Option<i64>) -> Result<(), Error> {
+    if reporting_interval.is_some_and(|interval| interval <= 0) {
+        return Err(ErrorBadRequest("Reporting interval must be positive"));
+    }
+    Ok(())
+}
+
+async fn insert_and_enqueue(
+    db: &DatabaseConnection,
+    new_sensor: sensor::ActiveModel,
+) -> Result<(sensor::Model, Option<LiveEvent>), DbErr> {
+    let txn = db.begin().await?;
+    let entity = new_sensor.insert(&txn).await?;
+    let event = record(&txn, EventType::SensorCreated, Resource::Sensor(&entity)).await?;
+    txn.commit().await?;
+    Ok((entity, event))
+}
+
+/// Deletes a sensor and queues deletion events for it and its containers in
+/// one transaction.
+async fn delete_and_enqueue(db: &DatabaseConnection, id: &str) -> Result<Vec<LiveEvent>, DbErr> {
+    let txn = db.begin().await?;
+    let mut events = Vec::new();
+    if let Some(entity) = Sensor::find_by_id(id).one(&txn).await? {
+        for container in entity.find_related(DataContainer).all(&txn).await? {
+            let resource = Resource::DataContainer(&container);
+            events.extend(record(&txn, EventType::DataContainerDeleted, resource).await?);
+        }
+        events.extend(record(&txn, EventType::SensorDeleted, Resource::Sensor(&entity)).await?);
+        Sensor::delete_by_id(id).exec(&txn).await?;
+    }
+    txn.commit().await?;
+    Ok(events)
+}
+
 #[post("")]
 async fn create_sensor(
     state: Data<AppState>,
@@ -41,17 +116,42 @@ async fn create_sensor(
     let SensorCreate {
         name,
         application_id,
+        reporting_interval,
+        kind,
+        units,
+        latitude,
+        longitude,
+        manufacturer,
+        model_name,
+        serial_number,
+        tags,
     } = body.into_inner();
+    check_reporting_interval(reporting_interval)?;
+    if let Some(units) = &units {
+        check_units(units).map_err(ErrorBadRequest)?;
+    }
+    check_location(latitude, longitude).map_err(ErrorBadRequest)?;
+    let tags = normalize_tags(tags).map_err(ErrorBadRequest)?;
 
     let new_sensor = sensor::ActiveModel {
         id: sea_orm::ActiveValue::Set(nanoid!(10)),
         name: sea_orm::ActiveValue::Set(name.to_owned()),
         application_id: sea_orm::ActiveValue::Set(application_id.to_owned()),
+        reporting_interval: sea_orm::ActiveValue::Set(reporting_interval),
+        kind: sea_orm::ActiveValue::Set(kind),
+        units: sea_orm::ActiveValue::Set(units),
+        latitude: sea_orm::ActiveValue::Set(latitude),
+        longitude: sea_orm::ActiveValue::Set(longitude),
+        manufacturer: sea_orm::ActiveValue::Set(manufacturer),
+        model_name: sea_orm::ActiveValue::Set(model_name),
+        serial_number: sea_orm::ActiveValue::Set(serial_number),
+        tags: sea_orm::ActiveValue::Set(tags),
         ..Default::default()
     };
 
-    match new_sensor.insert(&state.db).await {
-        Ok(entity) => {
+    match insert_and_enqueue(&state.db, new_sensor).await {
+        Ok((entity, event)) => {
+            state.live.publish(event).await;
             let mut redis_conn = state
                 .redis
                 .get_multiplexed_tokio_connection()
@@ -92,10 +192,10 @@ async fn get_sensor(
         .await
         .unwrap();
 
-    if let Ok(cached_sensor) = redis_conn.get::<_, String>(get_redis_id(PREFIX, &id)).await {
-        if let Ok(sensor) = serde_json::from_str::<sensor::Model>(&cached_sensor) {
-            return Ok(Json(sensor));
-        }
+    if let Ok(cached_sensor) = redis_conn.get::<_, String>(get_redis_id(PREFIX, &id)).await
+        && let Ok(sensor) = serde_json::from_str::<sensor::Model>(&cached_sensor)
+    {
+        return Ok(Json(sensor));
     }
 
     match Sensor::find_by_id(&id).one(&state.db).await {
@@ -140,6 +240,28 @@ async fn get_sensor_data_container(
     }
 }
 
+/// Readings of all the sensor's containers, paged or exported like
+/// `/data_container/{id}/sensor_data`.
+#[get("/{id}/sensor_data")]
+async fn get_sensor_data(
+    state: Data<AppState>,
+    params: Path<RUDSensorParams>,
+    query: Query<SensorDataQuery>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let RUDSensorParams { id } = params.into_inner();
+    list_sensor_data(&state, Source::Sensor(&id), &query, &req).await
+}
+
+#[get("/{id}/subscribers")]
+async fn get_subscribers(
+    state: Data<AppState>,
+    params: Path<RUDSensorParams>,
+) -> Result<Json<Vec<subscribers::Model>>, Error> {
+    let RUDSensorParams { id } = params.into_inner();
+    list_subscribers(&state, subscribers::Column::SensorId, &id).await
+}
+
 #[patch("/{id}")]
 async fn update_sensor(
     state: Data<AppState>,
@@ -147,12 +269,68 @@ async fn update_sensor(
     body: Json<SensorUpdate>,
 ) -> Result<Json<sensor::Model>, Error> {
     let RUDSensorParams { id } = params.into_inner();
-    let SensorUpdate { name } = body.into_inner();
+    let SensorUpdate {
+        name,
+        reporting_interval,
+        kind,
+        units,
+        latitude,
+        longitude,
+        manufacturer,
+        model_name,
+        serial_number,
+        tags,
+    } = body.into_inner();
+    check_reporting_interval(reporting_interval.flatten())?;
+    if let Some(Some(units)) = &units {
+        check_units(units).map_err(ErrorBadRequest)?;
+    }
+    let tags = tags
+        .map(normalize_tags)
+        .transpose()
+        .map_err(ErrorBadRequest)?;
 
     match Sensor::find_by_id(&id).one(&state.db).await {
         Ok(Some(e)) => {
+            check_location(
+                latitude.unwrap_or(e.latitude),
+                longitude.unwrap_or(e.longitude),
+            )
+            .map_err(ErrorBadRequest)?;
             let mut entity: sensor::ActiveModel = e.into();
-            entity.name = sea_orm::ActiveValue::Set(name.to_owned());
+            if let Some(name) = name {
+                entity.name = sea_orm::ActiveValue::Set(name);
+            }
+            if let Some(reporting_interval) = reporting_interval {
+                entity.reporting_interval = sea_orm::ActiveValue::Set(reporting_interval);
+                if reporting_interval.is_none() {
+                    entity.offline_since = sea_orm::ActiveValue::Set(None);
+                }
+            }
+            if let Some(kind) = kind {
+                entity.kind = sea_orm::ActiveValue::Set(kind);
+            }
+            if let Some(units) = units {
+                entity.units = sea_orm::ActiveValue::Set(units);
+            }
+            if let Some(latitude) = latitude {
+                entity.latitude = sea_orm::ActiveValue::Set(latitude);
+            }
+            if let Some(longitude) = longitude {
+                entity.longitude = sea_orm::ActiveValue::Set(longitude);
+            }
+            if let Some(manufacturer) = manufacturer {
+                entity.manufacturer = sea_orm::ActiveValue::Set(manufacturer);
+            }
+            if let Some(model_name) = model_name {
+                entity.model_name = sea_orm::ActiveValue::Set(model_name);
+            }
+            if let Some(serial_number) = serial_number {
+                entity.serial_number = sea_orm::ActiveValue::Set(serial_number);
+            }
+            if let Some(tags) = tags {
+                entity.tags = sea_orm::ActiveValue::Set(tags);
+            }
             match entity.update(&state.db).await {
                 Ok(updated_entity) => {
                     let mut redis_conn = state
@@ -191,8 +369,9 @@ async fn delete_sensor(
 ) -> Result<&'static str, Error> {
     let RUDSensorParams { id } = params.into_inner();
 
-    match Sensor::delete_by_id(&id).exec(&state.db).await {
-        Ok(_) => {
+    match delete_and_enqueue(&state.db, &id).await {
+        Ok(events) => {
+            state.live.publish(events).await;
             let mut redis_conn = state
                 .redis
                 .get_multiplexed_tokio_connection()
@@ -212,6 +391,8 @@ pub fn add_sensor_route(cfg: &mut ServiceConfig) {
     cfg.service(create_sensor)
         .service(get_sensor)
         .service(get_sensor_data_container)
+        .service(get_sensor_data)
+        .service(get_subscribers)
         .service(update_sensor)
         .service(delete_sensor);
 }
@@ -1,27 +1,89 @@
 use actix_web::{
-    Error, delete,
-    error::{ErrorBadRequest, ErrorInternalServerError},
+    Error, HttpRequest, delete,
+    error::{ErrorBadRequest, ErrorConflict, ErrorInternalServerError},
     get, post,
-    web::{Data, Json, Path, ServiceConfig},
+    web::{Data, Json, JsonConfig, Path, ServiceConfig},
 };
+use chrono::{DateTime, Utc};
+use jsonschema::Validator;
 use nanoid::nanoid;
-use redis::AsyncCommands;
-use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, SqlErr};
-use serde::Deserialize;
+use redis::{AsyncCommands, RedisResult, aio::MultiplexedConnection};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter, SqlErr,
+    TransactionTrait,
+};
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::collections::{HashMap, HashSet};
 
 use crate::{
-    AppState,
+    AppState, alarm,
+    capacity::evict,
     entities::{prelude::*, *},
-    utils::{get_redis_id, get_redis_set_options},
+    events::{record, record_batch},
+    idempotency::{self, Claim},
+    live::LiveEvent,
+    liveness::touch,
+    notification::{envelope::EventType, scope::Resource},
+    routes::{
+        DataContainer::{LATEST_PREFIX, cache_latest_reading},
+        Sensor::PREFIX as SENSOR_PREFIX,
+    },
+    schema::{self, Violation},
+    utils::{get_redis_id, get_redis_set_options, optional_timestamp},
 };
 
 const PREFIX: &str = "SensorData";
+const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+const MAX_BATCH_SIZE: usize = 1000;
+/// Room for a full batch; actix' default JSON limit is 32 KiB.
+const MAX_BODY_SIZE: usize = 4 * 1024 * 1024;
 
 #[derive(Deserialize)]
-struct SensorDataCreate {
+pub struct SensorDataCreate {
     pub container_id: String,
     pub data: Value,
+    /// When the device took the reading; defaults to the time of ingestion.
+    #[serde(default, deserialize_with = "optional_timestamp")]
+    pub measured_at: Option<DateTime<Utc>>,
+    /// Client-chosen id; a retry with the same id returns the stored reading.
+    pub message_id: Option<String>,
+}
+
+/// Outcome of one reading of a batch, in request order.
+#[derive(Clone, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+enum BatchItemResult {
+    Created {
+        reading: sensor_data::Model,
+    },
+    /// The message id was seen before; this is the reading it created.
+    Replayed {
+        reading: sensor_data::Model,
+    },
+    Rejected {
+        error: &'static str,
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        violations: Vec<Violation>,
+    },
+}
+
+impl BatchItemResult {
+    fn rejected(error: &'static str) -> Self {
+        BatchItemResult::Rejected {
+            error,
+            violations: Vec::new(),
+        }
+    }
+}
+
+/// A batch item's result, or the sensor data id to resolve it from.
+enum Slot {
+    Done(BatchItemResult),
+    Created(String),
+    Replayed(String),
+    /// Repeats the message id of the batch item at this index.
+    SameAs(usize),
 }
 
 #[derive(Deserialize)]
@@ -29,47 +91,173 @@ struct RDSensorDataParams {
     id: String,
 }
 
-#[post("")]
-async fn create_sensor_data(
-    state: Data<AppState>,
-    body: Json<SensorDataCreate>,
-) -> Result<Json<sensor_data::Model>, Error> {
-    let SensorDataCreate { container_id, data } = body.into_inner();
+/// Stores a reading, evaluates alarm rules, marks its sensor as seen, queues
+/// `sensor_data.created` and evicts whatever the container's capacity limits
+/// no longer allow, in one transaction. Returns the reading, the evicted ids
+/// and the live events.
+async fn insert_and_enqueue(
+    db: &DatabaseConnection,
+    container: &data_container::Model,
+    new_sensor_data: sensor_data::ActiveModel,
+) -> Result<(sensor_data::Model, Vec<String>, Vec<LiveEvent>), DbErr> {
+    let txn = db.begin().await?;
+    let entity = new_sensor_data.insert(&txn).await?;
+    // Alarm rules lock first, the same order retiring a rule takes.
+    let mut events = alarm::evaluate(&txn, container, &entity).await?;
+    events.extend(touch(&txn, &[&container.sensor_id]).await?);
+    let resource = Resource::SensorData(&entity);
+    events.extend(record(&txn, EventType::SensorDataCreated, resource).await?);
+    let (evicted, eviction_events) = evict(&txn, container).await?;
+    events.extend(eviction_events);
+    txn.commit().await?;
+    Ok((entity, evicted, events))
+}
+
+/// Stores a batch of readings, evaluates alarm rules container by container
+/// and reading by reading in measurement order, marks their sensors as seen,
+/// queues one coalesced notification per subscriber and evicts over-capacity
+/// readings, in one transaction.
+async fn insert_batch_and_enqueue(
+    db: &DatabaseConnection,
+    containers: &HashMap<String, data_container::Model>,
+    new_sensor_data: Vec<sensor_data::ActiveModel>,
+) -> Result<(Vec<sensor_data::Model>, Vec<String>, Vec<LiveEvent>), DbErr> {
+    let txn = db.begin().await?;
+    let entities = SensorData::insert_many(new_sensor_data)
+        .exec_with_returning_many(&txn)
+        .await?;
+    let touched: HashSet<&str> = entities
+        .iter()
+        .map(|entity| entity.container_id.as_str())
+        .collect();
+    let sensor_ids: Vec<&str> = touched
+        .iter()
+        .map(|container_id| containers[*container_id].sensor_id.as_str())
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    // Alarm rules lock first, and containers in id order, so concurrent
+    // batches and rule retirement take their locks in the same order.
+    let mut measured: Vec<&sensor_data::Model> = entities.iter().collect();
+    measured.sort_by_key(|entity| (&entity.container_id, entity.measured_at));
+    let mut events = Vec::new();
+    for entity in measured {
+        let container = &containers[entity.container_id.as_str()];
+        events.extend(alarm::evaluate(&txn, container, entity).await?);
+    }
+    events.extend(touch(&txn, &sensor_ids).await?);
+    events.extend(record_batch(&txn, &entities).await?);
+    let mut evicted = Vec::new();
+    for container_id in touched {
+        let (ids, eviction_events) = evict(&txn, &containers[container_id]).await?;
+        evicted.extend(ids);
+        events.extend(eviction_events);
+    }
+    txn.commit().await?;
+    Ok((entities, evicted, events))
+}
+
+/// Deletes a reading and queues `sensor_data.deleted` in one transaction.
+/// Returns the deleted reading, if it existed, with its live event.
+async fn delete_and_enqueue(
+    db: &DatabaseConnection,
+    id: &str,
+) -> Result<Option<(sensor_data::Model, Option<LiveEvent>)>, DbErr> {
+    let txn = db.begin().await?;
+    let Some(entity) = SensorData::find_by_id(id).one(&txn).await? else {
+        return Ok(None);
+    };
+    let resource = Resource::SensorData(&entity);
+    let event = record(&txn, EventType::SensorDataDeleted, resource).await?;
+    SensorData::delete_by_id(id).exec(&txn).await?;
+    txn.commit().await?;
+    Ok(Some((entity, event)))
+}
+
+/// Stores a reading, queues its notifications and publishes it to live
+/// consumers. Shared by `POST /sensor_data` and the WebSocket API.
+pub async fn ingest_sensor_data(
+    state: &AppState,
+    body: SensorDataCreate,
+) -> Result<sensor_data::Model, Error> {
+    let SensorDataCreate {
+        container_id,
+        data,
+        measured_at,
+        message_id,
+    } = body;
+
+    let container = match DataContainer::find_by_id(&container_id)
+        .one(&state.db)
+        .await
+    {
+        Ok(Some(container)) => container,
+        Ok(None) => return Err(ErrorBadRequest("Can't find data container")),
+        Err(e) => {
+            eprintln!("Error fetching data container: {:?}", e);
+            return Err(ErrorInternalServerError("Query failed"));
+        }
+    };
+    if let Some(schema) = &container.schema {
+        schema::validate(schema, &data).map_err(|violations| schema::rejection(&violations))?;
+    }
+
+    // Shared with the WebSocket ingest, where a panic would drop the
+    // connection, so Redis errors are returned or logged instead.
+    let mut redis_conn = state
+        .redis
+        .get_multiplexed_tokio_connection()
+        .await
+        .map_err(|e| {
+            eprintln!("Error connecting to Redis: {:?}", e);
+            ErrorInternalServerError("Query failed")
+        })?;
+    if let Some(message_id) = &message_id {
+        match idempotency::claim(&mut redis_conn, &container_id, message_id)
+            .await
+            .map_err(|e| {
+                eprintln!("Error claiming message id: {:?}", e);
+                ErrorInternalServerError("Query failed")
+            })? {
+            Claim::New => {}
+            Claim::Replay(id) => return find_replayed(state, &id).await,
+            Claim::InFlight => {
+                return Err(ErrorConflict(
+                    "A reading with this message id is still being stored",
+                ));
+            }
+        }
+    }
 
     let new_sensor_data = sensor_data::ActiveModel {
         id: sea_orm::ActiveValue::Set(nanoid!(21)),
         container_id: sea_orm::ActiveValue::Set(container_id.to_owned()),
         data: sea_orm::ActiveValue::Set(Some(data)),
+        measured_at: sea_orm::ActiveValue::Set(measured_at.unwrap_or_else(Utc::now).naive_utc()),
         ..Default::default()
     };
 
-    match new_sensor_data.insert(&state.db).await {
-        //TODO: Send new data to subscribers
-        Ok(entity) => {
-            let mut redis_conn = state
-                .redis
-                .get_multiplexed_tokio_connection()
-                .await
-                .unwrap();
-            let _: () = redis_conn
-                .set_options(
-                    get_redis_id(PREFIX, &entity.id),
-                    serde_json::to_string(&entity).unwrap(),
-                    get_redis_set_options(),
-                )
-                .await
-                .unwrap();
-            let subscriber_list = Subscribers::find()
-                .filter(subscribers::Column::ContainerId.eq(&entity.container_id))
-                .all(&state.db)
-                .await
-                .unwrap();
-            for subscriber in subscriber_list {
-                if let Ok(_) = surf::post(&subscriber.notification_url).await {
-                    // Do nothing
-                }
+    let result = insert_and_enqueue(&state.db, &container, new_sensor_data).await;
+    if let Some(message_id) = &message_id {
+        let settled = match &result {
+            Ok((entity, _, _)) => {
+                idempotency::complete(&mut redis_conn, &container_id, message_id, &entity.id).await
             }
-            Ok(Json(entity))
+            Err(_) => idempotency::release(&mut redis_conn, &container_id, message_id).await,
+        };
+        // The pending claim expires on its own.
+        if let Err(e) = settled {
+            eprintln!("Error settling message id: {:?}", e);
+        }
+    }
+
+    match result {
+        Ok((entity, evicted, events)) => {
+            state.live.publish(events).await;
+            if let Err(e) = cache_created(&mut redis_conn, &entity, &evicted, &container).await {
+                eprintln!("Error caching sensor data: {:?}", e);
+            }
+            Ok(entity)
         }
         Err(e) => match e.sql_err() {
             Some(SqlErr::ForeignKeyConstraintViolation(_)) => {
@@ -83,6 +271,333 @@ async fn create_sensor_data(
     }
 }
 
+/// Caches a stored reading as its container's latest, drops the evicted ones
+/// and the cached sensor, whose `last_seen_at` may have moved.
+async fn cache_created(
+    redis_conn: &mut MultiplexedConnection,
+    entity: &sensor_data::Model,
+    evicted: &[String],
+    container: &data_container::Model,
+) -> RedisResult<()> {
+    uncache(redis_conn, evicted).await?;
+    let _: () = redis_conn
+        .set_options(
+            get_redis_id(PREFIX, &entity.id),
+            serde_json::to_string(entity).unwrap(),
+            get_redis_set_options(),
+        )
+        .await?;
+    cache_latest_reading(redis_conn, entity).await?;
+    redis_conn
+        .del(get_redis_id(SENSOR_PREFIX, &container.sensor_id))
+        .await
+}
+
+/// The reading a replayed message id was first stored as.
+async fn find_replayed(state: &AppState, id: &str) -> Result<sensor_data::Model, Error> {
+    match SensorData::find_by_id(id).one(&state.db).await {
+        Ok(Some(entity)) => Ok(entity),
+        Ok(None) => Err(ErrorConflict(
+            "The reading stored for this message id has since been deleted",
+        )),
+        Err(e) => {
+            eprintln!("Error fetching sensor data: {:?}", e);
+            Err(ErrorInternalServerError("Query failed"))
+        }
+    }
+}
+
+/// A body `message_id` takes precedence over the `Idempotency-Key` header.
+#[post("")]
+async fn create_sensor_data(
+    state: Data<AppState>,
+    req: HttpRequest,
+    body: Json<SensorDataCreate>,
+) -> Result<Json<sensor_data::Model>, Error> {
+    let mut body = body.into_inner();
+    if body.message_id.is_none() {
+        body.message_id = idempotency_key(&req);
+    }
+    ingest_sensor_data(&state, body).await.map(Json)
+}
+
+fn idempotency_key(req: &HttpRequest) -> Option<String> {
+    req.headers()
+        .get(IDEMPOTENCY_KEY_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_owned)
+}
+
+/// Ingests readings buffered by a gateway, across any number of containers.
+/// Invalid readings are rejected individually; the valid ones are stored
+/// together. An item repeating an earlier item's message id is stored once.
+#[post("/batch")]
+async fn create_sensor_data_batch(
+    state: Data<AppState>,
+    body: Json<Vec<SensorDataCreate>>,
+) -> Result<Json<Vec<BatchItemResult>>, Error> {
+    let readings = body.into_inner();
+    if readings.len() > MAX_BATCH_SIZE {
+        return Err(ErrorBadRequest(format!(
+            "A batch holds at most {} readings",
+            MAX_BATCH_SIZE
+        )));
+    }
+
+    let container_ids: HashSet<&str> = readings
+        .iter()
+        .map(|reading| reading.container_id.as_str())
+        .collect();
+    let containers: HashMap<String, data_container::Model> = match DataContainer::find()
+        .filter(data_container::Column::Id.is_in(container_ids))
+        .all(&state.db)
+        .await
+    {
+        Ok(containers) => containers
+            .into_iter()
+            .map(|container| (container.id.to_owned(), container))
+            .collect(),
+        Err(e) => {
+            eprintln!("Error fetching data containers: {:?}", e);
+            return Err(ErrorInternalServerError("Query failed"));
+        }
+    };
+
+    // Compiled once per container rather than once per reading.
+    let validators: HashMap<&str, Option<Validator>> = containers
+        .values()
+        .map(|container| {
+            let validator = container.schema.as_ref().and_then(schema::compile);
+            (container.id.as_str(), validator)
+        })
+        .collect();
+
+    let mut redis_conn = state
+        .redis
+        .get_multiplexed_tokio_connection()
+        .await
+        .map_err(|e| {
+            eprintln!("Error connecting to Redis: {:?}", e);
+            ErrorInternalServerError("Query failed")
+        })?;
+    let mut slots = Vec::with_capacity(readings.len());
+    let mut claimed = Vec::new();
+    let mut first_with_message_id: HashMap<(String, String), usize> = HashMap::new();
+    let mut new_sensor_data = Vec::new();
+    for SensorDataCreate {
+        container_id,
+        data,
+        measured_at,
+        message_id,
+    } in readings
+    {
+        if let Some(message_id) = &message_id {
+            let key = (container_id.to_owned(), message_id.to_owned());
+            if let Some(index) = first_with_message_id.get(&key) {
+                slots.push(Slot::SameAs(*index));
+                continue;
+            }
+            first_with_message_id.insert(key, slots.len());
+        }
+        let Some(validator) = validators.get(container_id.as_str()) else {
+            slots.push(Slot::Done(BatchItemResult::rejected(
+                "Can't find data container",
+            )));
+            continue;
+        };
+        if let Some(validator) = validator
+            && let Err(violations) = schema::validate_with(validator, &data)
+        {
+            slots.push(Slot::Done(BatchItemResult::Rejected {
+                error: schema::REJECTION,
+                violations,
+            }));
+            continue;
+        }
+        let id = nanoid!(21);
+        if let Some(message_id) = message_id {
+            let claim = match idempotency::claim(&mut redis_conn, &container_id, &message_id).await
+            {
+                Ok(claim) => claim,
+                Err(e) => {
+                    eprintln!("Error claiming message id: {:?}", e);
+                    release(&mut redis_conn, &claimed).await;
+                    return Err(ErrorInternalServerError("Query failed"));
+                }
+            };
+            match claim {
+                Claim::New => claimed.push((container_id.to_owned(), message_id, id.to_owned())),
+                Claim::Replay(id) => {
+                    slots.push(Slot::Replayed(id));
+                    continue;
+                }
+                Claim::InFlight => {
+                    slots.push(Slot::Done(BatchItemResult::rejected(
+                        "A reading with this message id is still being stored",
+                    )));
+                    continue;
+                }
+            }
+        }
+
+        slots.push(Slot::Created(id.to_owned()));
+        new_sensor_data.push(sensor_data::ActiveModel {
+            id: sea_orm::ActiveValue::Set(id),
+            container_id: sea_orm::ActiveValue::Set(container_id),
+            data: sea_orm::ActiveValue::Set(Some(data)),
+            measured_at: sea_orm::ActiveValue::Set(
+                measured_at.unwrap_or_else(Utc::now).naive_utc(),
+            ),
+            ..Default::default()
+        });
+    }
+
+    let result = if new_sensor_data.is_empty() {
+        Ok((Vec::new(), Vec::new(), Vec::new()))
+    } else {
+        insert_batch_and_enqueue(&state.db, &containers, new_sensor_data).await
+    };
+    let created: HashMap<String, sensor_data::Model> = match result {
+        Ok((entities, evicted, events)) => {
+            // The readings are stored, so Redis errors are only logged; a
+            // pending claim expires on its own.
+            for (container_id, message_id, id) in &claimed {
+                if let Err(e) =
+                    idempotency::complete(&mut redis_conn, container_id, message_id, id).await
+                {
+                    eprintln!("Error settling message id: {:?}", e);
+                }
+            }
+            state.live.publish(events).await;
+            if let Err(e) = cache_latest(&mut redis_conn, &containers, &entities, &evicted).await {
+                eprintln!("Error caching sensor data: {:?}", e);
+            }
+            entities
+                .into_iter()
+                .map(|entity| (entity.id.to_owned(), entity))
+                .collect()
+        }
+        Err(e) => {
+            release(&mut redis_conn, &claimed).await;
+            return match e.sql_err() {
+                Some(SqlErr::ForeignKeyConstraintViolation(_)) => {
+                    Err(ErrorBadRequest("Can't find data container"))
+                }
+                _ => {
+                    eprintln!("Error creating sensor data: {:?}", e);
+                    Err(ErrorInternalServerError("Query failed"))
+                }
+            };
+        }
+    };
+
+    let replayed_ids: Vec<&String> = slots
+        .iter()
+        .filter_map(|slot| match slot {
+            Slot::Replayed(id) => Some(id),
+            _ => None,
+        })
+        .collect();
+    let replayed: HashMap<String, sensor_data::Model> = if replayed_ids.is_empty() {
+        HashMap::new()
+    } else {
+        match SensorData::find()
+            .filter(sensor_data::Column::Id.is_in(replayed_ids))
+            .all(&state.db)
+            .await
+        {
+            Ok(entities) => entities
+                .into_iter()
+                .map(|entity| (entity.id.to_owned(), entity))
+                .collect(),
+            Err(e) => {
+                eprintln!("Error fetching sensor data: {:?}", e);
+                return Err(ErrorInternalServerError("Query failed"));
+            }
+        }
+    };
+
+    let mut results: Vec<BatchItemResult> = Vec::with_capacity(slots.len());
+    for slot in slots {
+        let result = match slot {
+            Slot::Done(result) => result,
+            Slot::Created(id) => BatchItemResult::Created {
+                reading: created[&id].to_owned(),
+            },
+            Slot::Replayed(id) => match replayed.get(&id) {
+                Some(entity) => BatchItemResult::Replayed {
+                    reading: entity.to_owned(),
+                },
+                None => BatchItemResult::rejected(
+                    "The reading stored for this message id has since been deleted",
+                ),
+            },
+            // A repeat within the batch replays whatever the first item got.
+            Slot::SameAs(index) => match &results[index] {
+                BatchItemResult::Created { reading } | BatchItemResult::Replayed { reading } => {
+                    BatchItemResult::Replayed {
+                        reading: reading.to_owned(),
+                    }
+                }
+                rejected => rejected.clone(),
+            },
+        };
+        results.push(result);
+    }
+    Ok(Json(results))
+}
+
+/// Drops deleted readings, e.g. evicted or archived ones, from the reading
+/// cache.
+pub async fn uncache(redis_conn: &mut MultiplexedConnection, ids: &[String]) -> RedisResult<()> {
+    if ids.is_empty() {
+        return Ok(());
+    }
+    let keys: Vec<String> = ids.iter().map(|id| get_redis_id(PREFIX, id)).collect();
+    redis_conn.del(keys).await
+}
+
+/// Releases the message ids claimed for a batch that was not stored. Errors
+/// are only logged, as the pending claims expire on their own.
+async fn release(redis_conn: &mut MultiplexedConnection, claimed: &[(String, String, String)]) {
+    for (container_id, message_id, _) in claimed {
+        if let Err(e) = idempotency::release(redis_conn, container_id, message_id).await {
+            eprintln!("Error settling message id: {:?}", e);
+        }
+    }
+}
+
+/// Drops the evicted readings, refreshes the latest-reading cache of every
+/// container in the batch and drops the cached sensors, whose `last_seen_at`
+/// may have moved.
+async fn cache_latest(
+    redis_conn: &mut MultiplexedConnection,
+    containers: &HashMap<String, data_container::Model>,
+    entities: &[sensor_data::Model],
+    evicted: &[String],
+) -> RedisResult<()> {
+    uncache(redis_conn, evicted).await?;
+    let mut latest: HashMap<&str, &sensor_data::Model> = HashMap::new();
+    for entity in entities {
+        let current = latest.entry(&entity.container_id).or_insert(entity);
+        if (&entity.measured_at, &entity.id) > (&current.measured_at, &current.id) {
+            *current = entity;
+        }
+    }
+
+    let mut sensor_ids = HashSet::new();
+    for (container_id, entity) in latest {
+        cache_latest_reading(redis_conn, entity).await?;
+        sensor_ids.insert(&containers[container_id].sensor_id);
+    }
+    for sensor_id in sensor_ids {
+        let _: () = redis_conn
+            .del(get_redis_id(SENSOR_PREFIX, sensor_id))
+            .await?;
+    }
+    Ok(())
+}
+
 #[get("/{id}")]
 async fn get_sensor_data(
     state: Data<AppState>,
@@ -96,10 +611,10 @@ async fn get_sensor_data(
         .await
         .unwrap();
 
-    if let Ok(cached_data) = redis_conn.get::<_, String>(get_redis_id(PREFIX, &id)).await {
-        if let Ok(entity) = serde_json::from_str::<sensor_data::Model>(&cached_data) {
-            return Ok(Json(entity));
-        }
+    if let Ok(cached_data) = redis_conn.get::<_, String>(get_redis_id(PREFIX, &id)).await
+        && let Ok(entity) = serde_json::from_str::<sensor_data::Model>(&cached_data)
+    {
+        return Ok(Json(entity));
     }
 
     match SensorData::find_by_id(&id).one(&state.db).await {
@@ -129,14 +644,21 @@ async fn delete_sensor_data(
 ) -> Result<&'static str, Error> {
     let RDSensorDataParams { id } = params.into_inner();
 
-    match SensorData::delete_by_id(&id).exec(&state.db).await {
-        Ok(_) => {
+    match delete_and_enqueue(&state.db, &id).await {
+        Ok(deleted) => {
             let mut redis_conn = state
                 .redis
                 .get_multiplexed_tokio_connection()
                 .await
                 .unwrap();
             let _: () = redis_conn.del(get_redis_id(PREFIX, &id)).await.unwrap();
+            if let Some((entity, event)) = deleted {
+                state.live.publish(event).await;
+                let _: () = redis_conn
+                    .del(get_redis_id(LATEST_PREFIX, &entity.container_id))
+                    .await
+                    .unwrap();
+            }
             Ok("Sensor data deleted successfully")
         }
         Err(e) => {
@@ -147,7 +669,9 @@ async fn delete_sensor_data(
 }
 
 pub fn add_sensor_data_route(cfg: &mut ServiceConfig) {
-    cfg.service(create_sensor_data)
+    cfg.app_data(JsonConfig::default().limit(MAX_BODY_SIZE))
+        .service(create_sensor_data)
+        .service(create_sensor_data_batch)
         .service(get_sensor_data)
         .service(delete_sensor_data);
 }
@@ -1,31 +1,74 @@
 use actix_web::{
     Error, delete,
     error::{ErrorBadRequest, ErrorInternalServerError},
-    get, patch, post,
-    web::{Data, Json, Path, ServiceConfig},
+    get, patch, post, rt,
+    web::{Data, Json, Path, Query, ServiceConfig},
 };
+use chrono::NaiveDateTime;
 use nanoid::nanoid;
-use redis::AsyncCommands;
-use sea_orm::{ActiveModelTrait, EntityTrait, SqlErr};
-use serde::Deserialize;
+use redis::{AsyncCommands, RedisResult};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, DbErr, EntityTrait, FromQueryResult,
+    PaginatorTrait, QueryFilter, QueryOrder, QuerySelect, SqlErr, sea_query::Expr,
+};
+use serde::{Deserialize, Serialize};
 
 use crate::{
     AppState,
-    entities::{prelude::*, *},
+    entities::{
+        prelude::*,
+        sea_orm_active_enums::{NotificationContent, SubscriptionStatus},
+        *,
+    },
+    notification::{
+        criteria::Criteria, http_client, signature::generate_secret, verification::verify,
+    },
     utils::{get_redis_id, get_redis_set_options},
 };
 
 const PREFIX: &str = "Subscriber";
+const DEFAULT_DELIVERY_LIMIT: u64 = 100;
+const MAX_DELIVERY_LIMIT: u64 = 1000;
+const MIN_SECRET_LENGTH: usize = 16;
 
 #[derive(Deserialize)]
 struct SubscriberCreate {
-    container_id: String,
+    home_id: Option<String>,
+    application_id: Option<String>,
+    sensor_id: Option<String>,
+    container_id: Option<String>,
     notification_url: String,
+    notification_content: Option<NotificationContent>,
+    criteria: Option<Criteria>,
+    /// Signing secret chosen by the client, so it can check the signature of
+    /// the verification challenge. Generated when absent.
+    secret: Option<String>,
 }
 
 #[derive(Deserialize)]
 struct SubscriberUpdate {
-    notification_url: String,
+    notification_url: Option<String>,
+    notification_content: Option<NotificationContent>,
+    criteria: Option<Criteria>,
+    #[serde(default)]
+    rotate_secret: bool,
+}
+
+/// A subscriber with its signing secret. The secret is never serialized with
+/// the subscriber itself, so it is only sent when created or rotated.
+#[derive(Serialize)]
+struct SubscriberWithSecret {
+    #[serde(flatten)]
+    subscriber: subscribers::Model,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    secret: Option<String>,
+}
+
+impl SubscriberWithSecret {
+    fn new(subscriber: subscribers::Model, with_secret: bool) -> Self {
+        let secret = with_secret.then(|| subscriber.secret.to_owned());
+        Self { subscriber, secret }
+    }
 }
 
 #[derive(Deserialize)]
@@ -33,20 +76,156 @@ struct RUDSubscriberParams {
     id: String,
 }
 
+#[derive(Deserialize)]
+struct DeliveryQuery {
+    limit: Option<u64>,
+    succeeded: Option<bool>,
+}
+
+#[derive(Serialize, FromQueryResult)]
+struct AttemptStats {
+    total: i64,
+    succeeded: i64,
+    failed: i64,
+    avg_latency_ms: Option<f64>,
+    last_succeeded_at: Option<NaiveDateTime>,
+    last_failed_at: Option<NaiveDateTime>,
+}
+
+#[derive(Serialize)]
+struct DeliveryStats {
+    #[serde(flatten)]
+    attempts: AttemptStats,
+    /// Notifications waiting for their first or next attempt.
+    queued: u64,
+    dead_lettered: u64,
+}
+
+async fn delivery_stats(db: &DatabaseConnection, id: &str) -> Result<DeliveryStats, DbErr> {
+    let attempts = NotificationDelivery::find()
+        .select_only()
+        .column_as(Expr::cust("COUNT(*)"), "total")
+        .column_as(Expr::cust("COUNT(*) FILTER (WHERE succeeded)"), "succeeded")
+        .column_as(
+            Expr::cust("COUNT(*) FILTER (WHERE NOT succeeded)"),
+            "failed",
+        )
+        .column_as(Expr::cust("AVG(latency_ms)::float8"), "avg_latency_ms")
+        .column_as(
+            Expr::cust("MAX(created_at) FILTER (WHERE succeeded)"),
+            "last_succeeded_at",
+        )
+        .column_as(
+            Expr::cust("MAX(created_at) FILTER (WHERE NOT succeeded)"),
+            "last_failed_at",
+        )
+        .filter(notification_delivery::Column::SubscriberId.eq(id))
+        .into_model::<AttemptStats>()
+        .one(db)
+        .await?
+        .ok_or_else(|| DbErr::RecordNotFound("Delivery stats".to_owned()))?;
+    let queued = NotificationQueue::find()
+        .filter(notification_queue::Column::SubscriberId.eq(id))
+        .count(db)
+        .await?;
+    let dead_lettered = DeadLetter::find()
+        .filter(dead_letter::Column::SubscriberId.eq(id))
+        .count(db)
+        .await?;
+
+    Ok(DeliveryStats {
+        attempts,
+        queued,
+        dead_lettered,
+    })
+}
+
+/// Runs the verification handshake in the background; the subscriber stays
+/// `pending` until it completes.
+fn spawn_verification(state: &AppState, subscriber: subscribers::Model) {
+    let state = state.clone();
+    rt::spawn(async move {
+        if let Err(e) = verify_subscriber(&state, &subscriber).await {
+            eprintln!("Error verifying subscriber: {:?}", e);
+        }
+    });
+}
+
+/// Stores the handshake's status unless the subscriber moved on meanwhile,
+/// i.e. its URL changed or another handshake already finished.
+async fn verify_subscriber(state: &AppState, subscriber: &subscribers::Model) -> Result<(), DbErr> {
+    let status = verify(&http_client(&state.notification), subscriber).await;
+    Subscribers::update_many()
+        .set(subscribers::ActiveModel {
+            status: sea_orm::ActiveValue::Set(status),
+            ..Default::default()
+        })
+        .filter(subscribers::Column::Id.eq(&subscriber.id))
+        .filter(subscribers::Column::NotificationUrl.eq(&subscriber.notification_url))
+        .filter(subscribers::Column::Status.eq(SubscriptionStatus::Pending))
+        .exec(&state.db)
+        .await?;
+
+    match state.redis.get_multiplexed_tokio_connection().await {
+        Ok(mut redis_conn) => {
+            let result: RedisResult<()> =
+                redis_conn.del(get_redis_id(PREFIX, &subscriber.id)).await;
+            if let Err(e) = result {
+                eprintln!("Error uncaching subscriber: {:?}", e);
+            }
+        }
+        Err(e) => eprintln!("Error uncaching subscriber: {:?}", e),
+    }
+    Ok(())
+}
+
 #[post("")]
 async fn create_subscriber(
     state: Data<AppState>,
     body: Json<SubscriberCreate>,
-) -> Result<Json<subscribers::Model>, Error> {
+) -> Result<Json<SubscriberWithSecret>, Error> {
     let SubscriberCreate {
+        home_id,
+        application_id,
+        sensor_id,
         container_id,
         notification_url,
+        notification_content,
+        criteria,
+        secret,
     } = body.into_inner();
 
+    let targets = [&home_id, &application_id, &sensor_id, &container_id];
+    if targets.iter().filter(|target| target.is_some()).count() != 1 {
+        return Err(ErrorBadRequest(
+            "Exactly one of home_id, application_id, sensor_id or container_id is required",
+        ));
+    }
+    if secret
+        .as_ref()
+        .is_some_and(|secret| secret.chars().count() < MIN_SECRET_LENGTH)
+    {
+        return Err(ErrorBadRequest(format!(
+            "Secret must be at least {} characters",
+            MIN_SECRET_LENGTH
+        )));
+    }
+
     let new_subscriber = subscribers::ActiveModel {
         id: sea_orm::ActiveValue::Set(nanoid!(10)),
         notification_url: sea_orm::ActiveValue::Set(notification_url.to_owned()),
-        container_id: sea_orm::ActiveValue::Set(container_id.to_owned()),
+        home_id: sea_orm::ActiveValue::Set(home_id),
+        application_id: sea_orm::ActiveValue::Set(application_id),
+        sensor_id: sea_orm::ActiveValue::Set(sensor_id),
+        container_id: sea_orm::ActiveValue::Set(container_id),
+        notification_content: sea_orm::ActiveValue::Set(
+            notification_content.unwrap_or(NotificationContent::Full),
+        ),
+        secret: sea_orm::ActiveValue::Set(secret.unwrap_or_else(generate_secret)),
+        status: sea_orm::ActiveValue::Set(SubscriptionStatus::Pending),
+        criteria: sea_orm::ActiveValue::Set(
+            serde_json::to_value(criteria.unwrap_or_default()).unwrap(),
+        ),
         ..Default::default()
     };
 
@@ -65,11 +244,12 @@ async fn create_subscriber(
                 )
                 .await
                 .unwrap();
-            Ok(Json(entity))
+            spawn_verification(&state, entity.clone());
+            Ok(Json(SubscriberWithSecret::new(entity, true)))
         }
         Err(e) => match e.sql_err() {
             Some(SqlErr::ForeignKeyConstraintViolation(_)) => {
-                Err(ErrorBadRequest("Can't find data container"))
+                Err(ErrorBadRequest("Can't find subscribed resource"))
             }
             _ => {
                 eprintln!("Error creating subscriber: {:?}", e);
@@ -91,10 +271,10 @@ async fn get_subscriber(
         .await
         .unwrap();
 
-    if let Ok(cached_subscriber) = redis_conn.get::<_, String>(get_redis_id(PREFIX, &id)).await {
-        if let Ok(entity) = serde_json::from_str::<subscribers::Model>(&cached_subscriber) {
-            return Ok(Json(entity));
-        }
+    if let Ok(cached_subscriber) = redis_conn.get::<_, String>(get_redis_id(PREFIX, &id)).await
+        && let Ok(entity) = serde_json::from_str::<subscribers::Model>(&cached_subscriber)
+    {
+        return Ok(Json(entity));
     }
 
     match Subscribers::find_by_id(&id).one(&state.db).await {
@@ -122,14 +302,37 @@ async fn update_subscriber(
     state: Data<AppState>,
     params: Path<RUDSubscriberParams>,
     body: Json<SubscriberUpdate>,
-) -> Result<Json<subscribers::Model>, Error> {
+) -> Result<Json<SubscriberWithSecret>, Error> {
     let RUDSubscriberParams { id } = params.into_inner();
-    let SubscriberUpdate { notification_url } = body.into_inner();
+    let SubscriberUpdate {
+        notification_url,
+        notification_content,
+        criteria,
+        rotate_secret,
+    } = body.into_inner();
 
     match Subscribers::find_by_id(&id).one(&state.db).await {
         Ok(Some(subscriber)) => {
+            let url_changed = notification_url
+                .as_ref()
+                .is_some_and(|url| *url != subscriber.notification_url);
             let mut subscriber: subscribers::ActiveModel = subscriber.into();
-            subscriber.notification_url = sea_orm::ActiveValue::Set(notification_url.to_owned());
+            if let Some(notification_url) = notification_url {
+                subscriber.notification_url = sea_orm::ActiveValue::Set(notification_url);
+            }
+            if url_changed {
+                subscriber.status = sea_orm::ActiveValue::Set(SubscriptionStatus::Pending);
+            }
+            if let Some(notification_content) = notification_content {
+                subscriber.notification_content = sea_orm::ActiveValue::Set(notification_content);
+            }
+            if let Some(criteria) = criteria {
+                subscriber.criteria =
+                    sea_orm::ActiveValue::Set(serde_json::to_value(criteria).unwrap());
+            }
+            if rotate_secret {
+                subscriber.secret = sea_orm::ActiveValue::Set(generate_secret());
+            }
             match subscriber.update(&state.db).await {
                 Ok(entity) => {
                     let mut redis_conn = state
@@ -145,7 +348,10 @@ async fn update_subscriber(
                         )
                         .await
                         .unwrap();
-                    Ok(Json(entity))
+                    if url_changed {
+                        spawn_verification(&state, entity.clone());
+                    }
+                    Ok(Json(SubscriberWithSecret::new(entity, rotate_secret)))
                 }
                 Err(e) => {
                     eprintln!("Error updating subscriber: {:?}", e);
@@ -161,6 +367,139 @@ async fn update_subscriber(
     }
 }
 
+/// Restarts the verification handshake, e.g. after a `failed` one.
+#[post("/{id}/verify")]
+async fn reverify_subscriber(
+    state: Data<AppState>,
+    params: Path<RUDSubscriberParams>,
+) -> Result<Json<subscribers::Model>, Error> {
+    let RUDSubscriberParams { id } = params.into_inner();
+
+    match Subscribers::update_many()
+        .set(subscribers::ActiveModel {
+            status: sea_orm::ActiveValue::Set(SubscriptionStatus::Pending),
+            ..Default::default()
+        })
+        .filter(subscribers::Column::Id.eq(&id))
+        .exec_with_returning(&state.db)
+        .await
+    {
+        Ok(entities) => match entities.into_iter().next() {
+            Some(entity) => {
+                let mut redis_conn = state
+                    .redis
+                    .get_multiplexed_tokio_connection()
+                    .await
+                    .unwrap();
+                let _: () = redis_conn
+                    .set_options(
+                        get_redis_id(PREFIX, &id),
+                        serde_json::to_string(&entity).unwrap(),
+                        get_redis_set_options(),
+                    )
+                    .await
+                    .unwrap();
+                spawn_verification(&state, entity.clone());
+                Ok(Json(entity))
+            }
+            None => Err(ErrorBadRequest("Subscriber not found")),
+        },
+        Err(e) => {
+            eprintln!("Error updating subscriber: {:?}", e);
+            Err(ErrorInternalServerError("Query failed"))
+        }
+    }
+}
+
+/// Subscribers attached directly to a home, application, sensor or container,
+/// for the `/{id}/subscribers` listings. Like every serialized subscriber they
+/// carry no secret.
+pub async fn list_subscribers(
+    state: &AppState,
+    column: subscribers::Column,
+    id: &str,
+) -> Result<Json<Vec<subscribers::Model>>, Error> {
+    match Subscribers::find()
+        .filter(column.eq(id))
+        .order_by_asc(subscribers::Column::CreateAt)
+        .all(&state.db)
+        .await
+    {
+        Ok(entities) => Ok(Json(entities)),
+        Err(e) => {
+            eprintln!("Error fetching subscribers: {:?}", e);
+            Err(ErrorInternalServerError("Query failed"))
+        }
+    }
+}
+
+/// Most recent delivery attempts first, optionally only failed or successful ones.
+#[get("/{id}/deliveries")]
+async fn get_deliveries(
+    state: Data<AppState>,
+    params: Path<RUDSubscriberParams>,
+    query: Query<DeliveryQuery>,
+) -> Result<Json<Vec<notification_delivery::Model>>, Error> {
+    let RUDSubscriberParams { id } = params.into_inner();
+    let DeliveryQuery { limit, succeeded } = query.into_inner();
+
+    match Subscribers::find_by_id(&id).one(&state.db).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return Err(ErrorBadRequest("Subscriber not found")),
+        Err(e) => {
+            eprintln!("Error fetching subscriber: {:?}", e);
+            return Err(ErrorInternalServerError("Query failed"));
+        }
+    }
+
+    let mut select =
+        NotificationDelivery::find().filter(notification_delivery::Column::SubscriberId.eq(id));
+    if let Some(succeeded) = succeeded {
+        select = select.filter(notification_delivery::Column::Succeeded.eq(succeeded));
+    }
+    match select
+        .order_by_desc(notification_delivery::Column::CreatedAt)
+        .limit(
+            limit
+                .unwrap_or(DEFAULT_DELIVERY_LIMIT)
+                .min(MAX_DELIVERY_LIMIT),
+        )
+        .all(&state.db)
+        .await
+    {
+        Ok(entities) => Ok(Json(entities)),
+        Err(e) => {
+            eprintln!("Error fetching deliveries: {:?}", e);
+            Err(ErrorInternalServerError("Query failed"))
+        }
+    }
+}
+
+#[get("/{id}/deliveries/stats")]
+async fn get_delivery_stats(
+    state: Data<AppState>,
+    params: Path<RUDSubscriberParams>,
+) -> Result<Json<DeliveryStats>, Error> {
+    let RUDSubscriberParams { id } = params.into_inner();
+
+    match Subscribers::find_by_id(&id).one(&state.db).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return Err(ErrorBadRequest("Subscriber not found")),
+        Err(e) => {
+            eprintln!("Error fetching subscriber: {:?}", e);
+            return Err(ErrorInternalServerError("Query failed"));
+        }
+    }
+
+    match delivery_stats(&state.db, &id).await {
+        Ok(stats) => Ok(Json(stats)),
+        Err(e) => {
+            eprintln!("Error fetching delivery stats: {:?}", e);
+            Err(ErrorInternalServerError("Query failed"))
+        }
+    }
+}
+
 #[delete("/{id}")]
 async fn delete_subscriber(
     state: Data<AppState>,
@@ -188,6 +527,9 @@ async fn delete_subscriber(
 pub fn add_subscriber_route(cfg: &mut ServiceConfig) {
     cfg.service(create_subscriber)
         .service(get_subscriber)
+        .service(get_deliveries)
+        .service(get_delivery_stats)
         .service(update_subscriber)
+        .service(reverify_subscriber)
         .service(delete_subscriber);
 }
@@ -1,3 +1,7 @@
+#![allow(non_snake_case)]
+
+pub mod Alarm;
+pub mod AlarmRule;
 pub mod Application;
 pub mod DataContainer;
 pub mod Home;
@@ -0,0 +1,77 @@
+//! JSON Schemas attached to data containers, checked against every reading.
+
+use actix_web::{Error, HttpResponse, error::InternalError};
+use jsonschema::Validator;
+use serde::Serialize;
+use serde_json::Value;
+
+pub const REJECTION: &str = "Sensor data does not match the data container schema";
+
+#[derive(Clone, Serialize)]
+pub struct Violation {
+    /// JSON Pointer into the reading's `data`, empty for the root.
+    pub path: String,
+    pub message: String,
+}
+
+#[derive(Serialize)]
+struct Rejection<'a> {
+    error: &'static str,
+    violations: &'a [Violation],
+}
+
+/// Checks that `schema` is a usable JSON Schema before it is stored.
+pub fn check(schema: &Value) -> Result<(), String> {
+    jsonschema::validator_for(schema)
+        .map(|_| ())
+        .map_err(|e| format!("Invalid JSON Schema: {}", e))
+}
+
+pub fn validate(schema: &Value, data: &Value) -> Result<(), Vec<Violation>> {
+    match compile(schema) {
+        Some(validator) => validate_with(&validator, data),
+        None => Ok(()),
+    }
+}
+
+/// Compiles a stored schema once for validating many readings.
+pub fn compile(schema: &Value) -> Option<Validator> {
+    match jsonschema::validator_for(schema) {
+        Ok(validator) => Some(validator),
+        // Schemas are checked when stored, so this only guards old rows.
+        Err(e) => {
+            eprintln!("Error compiling data container schema: {:?}", e);
+            None
+        }
+    }
+}
+
+pub fn validate_with(validator: &Validator, data: &Value) -> Result<(), Vec<Violation>> {
+    let violations: Vec<Violation> = validator
+        .iter_errors(data)
+        .map(|error| Violation {
+            path: error.instance_path.to_string(),
+            message: error.to_string(),
+        })
+        .collect();
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(violations)
+    }
+}
+
+/// 400 response listing every violation of the container's schema. Its
+/// message, used by the WebSocket API, lists them too.
+pub fn rejection(violations: &[Violation]) -> Error {
+    let message = violations
+        .iter()
+        .map(|violation| format!("{}: {}", violation.path, violation.message))
+        .collect::<Vec<_>>()
+        .join("; ");
+    let response = HttpResponse::BadRequest().json(Rejection {
+        error: REJECTION,
+        violations,
+    });
+    InternalError::from_response(format!("{}: {}", REJECTION, message), response).into()
+}
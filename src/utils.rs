@@ -1,9 +1,126 @@
+use chrono::{DateTime, Utc};
 use redis::{SetExpiry, SetOptions};
+use serde::{Deserialize, Deserializer, de::Error};
+use std::{env, str::FromStr};
 
 pub fn get_redis_id(route: &str, id: &String) -> String {
     format!("{}_{}", route, id)
 }
 
+/// Seconds cached entries live for.
+pub const REDIS_TTL_SECONDS: u64 = 30;
+
 pub fn get_redis_set_options() -> SetOptions {
-    SetOptions::default().with_expiration(SetExpiry::EX(30))
+    SetOptions::default().with_expiration(SetExpiry::EX(REDIS_TTL_SECONDS))
+}
+
+pub fn env_or<T: FromStr>(key: &str, default: T) -> T {
+    env::var(key)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
+
+/// Lets PATCH bodies tell an absent field (`None`) from an explicit `null`
+/// (`Some(None)`). Use with `#[serde(default, deserialize_with = "double_option")]`.
+pub fn double_option<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::deserialize(deserializer).map(Some)
+}
+
+/// Parses a device timestamp: RFC 3339 or milliseconds since the Unix epoch.
+pub fn parse_timestamp(raw: &str) -> Option<DateTime<Utc>> {
+    let raw = raw.trim();
+    match raw.parse::<i64>() {
+        Ok(millis) => DateTime::from_timestamp_millis(millis),
+        Err(_) => DateTime::parse_from_rfc3339(raw)
+            .ok()
+            .map(|timestamp| timestamp.to_utc()),
+    }
+}
+
+/// Optional timestamp field given as an RFC 3339 string or as epoch
+/// milliseconds, either a number or a string of digits.
+pub fn optional_timestamp<'de, D>(deserializer: D) -> Result<Option<DateTime<Utc>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Raw {
+        Millis(i64),
+        Text(String),
+    }
+
+    match Option::<Raw>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(Raw::Millis(millis)) => DateTime::from_timestamp_millis(millis)
+            .map(Some)
+            .ok_or_else(|| D::Error::custom(format!("Timestamp `{}` is out of range", millis))),
+        Some(Raw::Text(text)) => parse_timestamp(&text)
+            .map(Some)
+            .ok_or_else(|| D::Error::custom(format!("Invalid timestamp `{}`", text))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[test]
+    fn parses_rfc3339_and_epoch_millis() {
+        let expected = DateTime::parse_from_rfc3339("2025-01-02T03:04:05.678Z")
+            .unwrap()
+            .to_utc();
+        assert_eq!(parse_timestamp("2025-01-02T03:04:05.678Z"), Some(expected));
+        assert_eq!(
+            parse_timestamp(" 2025-01-02T05:04:05.678+02:00 "),
+            Some(expected)
+        );
+        assert_eq!(parse_timestamp("1735787045678"), Some(expected));
+        assert_eq!(parse_timestamp("0"), Some(DateTime::UNIX_EPOCH));
+    }
+
+    #[test]
+    fn rejects_other_timestamps() {
+        assert_eq!(parse_timestamp(""), None);
+        assert_eq!(parse_timestamp("2025-01-02"), None);
+        assert_eq!(parse_timestamp("2025-01-02 03:04:05"), None);
+        assert_eq!(parse_timestamp(&i64::MAX.to_string()), None);
+    }
+
+    #[derive(Deserialize)]
+    struct Reading {
+        #[serde(default, deserialize_with = "optional_timestamp")]
+        measured_at: Option<DateTime<Utc>>,
+    }
+
+    fn measured_at(body: &str) -> Result<Option<DateTime<Utc>>, serde_json::Error> {
+        serde_json::from_str::<Reading>(body).map(|reading| reading.measured_at)
+    }
+
+    #[test]
+    fn deserializes_optional_timestamps() {
+        let expected = DateTime::from_timestamp_millis(1735787045678);
+        assert_eq!(measured_at("{}").unwrap(), None);
+        assert_eq!(measured_at(r#"{ "measured_at": null }"#).unwrap(), None);
+        assert_eq!(
+            measured_at(r#"{ "measured_at": 1735787045678 }"#).unwrap(),
+            expected
+        );
+        assert_eq!(
+            measured_at(r#"{ "measured_at": "1735787045678" }"#).unwrap(),
+            expected
+        );
+        assert_eq!(
+            measured_at(r#"{ "measured_at": "2025-01-02T03:04:05.678Z" }"#).unwrap(),
+            expected
+        );
+        assert!(measured_at(r#"{ "measured_at": "soon" }"#).is_err());
+    }
 }